@@ -1,3 +1,4 @@
+use datafusion_common::arrow::datatypes::DataType;
 use thiserror::Error;
 
 pub type SqcResult<T> = Result<T, SqcError>;
@@ -10,6 +11,21 @@ pub enum SqcError {
     EmptyQuery,
     #[error("unsupported query")]
     UnsupportedQuery,
+    #[error("{0}")]
+    NotSupported(String),
+    #[error("{function} takes {expected} arguments, not {actual}")]
+    ArgumentCount {
+        function: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("recursive query {name} exceeded {max_recursion} iterations")]
+    MaxRecursion { name: String, max_recursion: usize },
+    #[error("{function} overflowed {data_type}")]
+    Overflow {
+        function: String,
+        data_type: DataType,
+    },
 }
 
 impl From<SqcError> for pyo3::PyErr {
@@ -0,0 +1,938 @@
+use std::{any::Any, borrow::Cow, cmp::Ordering, collections::HashSet, slice};
+
+use datafusion_common::{
+    arrow::datatypes::{DataType, DECIMAL128_MAX_PRECISION},
+    DFSchema, ScalarValue,
+};
+use datafusion_expr::{
+    expr::{self, AggregateFunctionDefinition},
+    AggregateFunction, Expr, ExprSchemable,
+};
+use pyo3::{types::PyDict, Bound};
+
+use crate::{
+    errors::SqcError,
+    udaf::{find_aggregate, FILTER_ARGUMENT},
+};
+
+use super::{
+    common::{
+        as_f64, compare_values, evaluate_expr, list_scalar, make_hash, numeric_supertype,
+        promote_numeric, struct_scalar,
+    },
+    sketch::{HyperLogLog, TDigest, DEFAULT_COMPRESSION},
+    sort::{as_sort_exprs, compare_keys},
+    ExecutionContext,
+};
+
+/// State of an aggregate function for one group, updated one row at a time
+pub trait Accumulator: Any {
+    /// Add the argument values of a row
+    fn update(&mut self, values: &[ScalarValue]);
+
+    /// Add the rows of `other`, the state of the same aggregate function
+    /// over other rows, e.g. of a finer group
+    fn merge(&mut self, other: &dyn Accumulator);
+
+    /// Result for the rows added so far
+    fn evaluate(&self) -> ScalarValue;
+
+    /// Error that fails the query instead of a result, e.g. an overflow
+    fn error(&self) -> Option<SqcError> {
+        None
+    }
+}
+
+impl dyn Accumulator {
+    /// The accumulator as its concrete type, for `merge`
+    pub fn downcast_ref<T: Accumulator>(&self) -> &T {
+        (self as &dyn Any)
+            .downcast_ref()
+            .expect("Accumulators of different aggregates can't be merged")
+    }
+}
+
+/// Create the accumulator of an aggregate function, `schema` is the schema of
+/// its input, which gives the types of the arguments
+pub fn create_accumulator(
+    aggfunc: &expr::AggregateFunction,
+    schema: &DFSchema,
+) -> Box<dyn Accumulator> {
+    let arg_types: Vec<_> = aggfunc
+        .args
+        .iter()
+        .map(|arg| match arg {
+            Expr::Wildcard { .. } => DataType::Null,
+            arg => arg.get_type(schema).unwrap(),
+        })
+        .collect();
+
+    let accumulator: Box<dyn Accumulator> = match &aggfunc.func_def {
+        AggregateFunctionDefinition::BuiltIn(fun) => match fun {
+            AggregateFunction::Sum => Box::new(Sum::new(&arg_types[0])),
+            AggregateFunction::Count => Box::new(Count(0)),
+            AggregateFunction::Avg => Box::new(Avg::default()),
+            AggregateFunction::Min => Box::new(Extremum::new(Ordering::Less)),
+            AggregateFunction::Max => Box::new(Extremum::new(Ordering::Greater)),
+            AggregateFunction::ArrayAgg => Box::new(ArrayAgg(OrderedRows::new(aggfunc))),
+            AggregateFunction::StringAgg => Box::new(StringAgg(OrderedRows::new(aggfunc))),
+            AggregateFunction::Stddev => Box::new(Variance::new(true, true)),
+            AggregateFunction::StddevPop => Box::new(Variance::new(false, true)),
+            AggregateFunction::Variance => Box::new(Variance::new(true, false)),
+            AggregateFunction::VariancePop => Box::new(Variance::new(false, false)),
+            AggregateFunction::Median => Box::new(Percentile::new(Some(0.5), true, true)),
+            AggregateFunction::Correlation => {
+                Box::new(Covariance::new(CovarianceKind::Correlation))
+            }
+            AggregateFunction::CovariancePop => {
+                Box::new(Covariance::new(CovarianceKind::Population))
+            }
+            AggregateFunction::BoolAnd => Box::new(BoolAgg::new(false)),
+            AggregateFunction::BoolOr => Box::new(BoolAgg::new(true)),
+            AggregateFunction::ApproxDistinct => Box::new(ApproxDistinct::default()),
+            AggregateFunction::ApproxPercentileCont => {
+                let compression = match aggfunc.args.get(2) {
+                    Some(Expr::Literal(compression)) => as_f64(compression).unwrap(),
+                    Some(arg) => panic!(
+                        "APPROX_PERCENTILE_CONT compression must be a literal, got {}",
+                        arg
+                    ),
+                    None => DEFAULT_COMPRESSION,
+                };
+                Box::new(ApproxPercentile::new(compression, &arg_types[0]))
+            }
+            _ => unimplemented!("Aggregate function {} is not implemented", fun),
+        },
+        AggregateFunctionDefinition::UDF(udf) => find_aggregate(udf.name())
+            .unwrap_or_else(|| panic!("Aggregate function {} is not implemented", udf.name()))
+            .accumulator(aggfunc, &arg_types),
+        func_def => unimplemented!("Aggregate function {} is not implemented", func_def.name()),
+    };
+
+    match aggfunc.distinct {
+        true => Box::new(Distinct {
+            seen: HashSet::new(),
+            distinct: vec![],
+            accumulator,
+        }),
+        false => accumulator,
+    }
+}
+
+/// The aggregate function with its `FILTER` clause, which the query passes
+/// to the aggregates of `udaf` as an extra, last argument marked by
+/// `FILTER_ARGUMENT`
+pub fn with_filter(aggfunc: &expr::AggregateFunction) -> Cow<'_, expr::AggregateFunction> {
+    match aggfunc.args.last() {
+        Some(Expr::ScalarFunction(function)) if function.name() == FILTER_ARGUMENT => {
+            let mut aggfunc = aggfunc.clone();
+            aggfunc.filter = Some(Box::new(function.args[0].clone()));
+            aggfunc.args.pop();
+            Cow::Owned(aggfunc)
+        }
+        _ => Cow::Borrowed(aggfunc),
+    }
+}
+
+/// Accumulators of the aggregates of `udaf` that are part of the executor,
+/// one constructor per aggregate
+pub mod standard {
+    use super::*;
+
+    pub fn variance(_aggfunc: &expr::AggregateFunction) -> Box<dyn Accumulator> {
+        Box::new(Variance::new(true, false))
+    }
+
+    pub fn percentile_cont(aggfunc: &expr::AggregateFunction) -> Box<dyn Accumulator> {
+        Box::new(percentile(aggfunc, true))
+    }
+
+    pub fn percentile_disc(aggfunc: &expr::AggregateFunction) -> Box<dyn Accumulator> {
+        Box::new(percentile(aggfunc, false))
+    }
+
+    fn percentile(aggfunc: &expr::AggregateFunction, continuous: bool) -> Percentile {
+        let asc = match aggfunc.order_by.as_deref() {
+            Some([Expr::Sort(sort)]) => sort.asc,
+            _ => panic!(
+                "{} needs WITHIN GROUP (ORDER BY expr)",
+                aggfunc.func_def.name()
+            ),
+        };
+        Percentile::new(None, continuous, asc)
+    }
+
+    pub fn covar_samp(_aggfunc: &expr::AggregateFunction) -> Box<dyn Accumulator> {
+        Box::new(Covariance::new(CovarianceKind::Sample))
+    }
+
+    pub fn json_object_agg(aggfunc: &expr::AggregateFunction) -> Box<dyn Accumulator> {
+        Box::new(JsonObjectAgg(OrderedRows::new(aggfunc)))
+    }
+
+    pub fn max_by(_aggfunc: &expr::AggregateFunction) -> Box<dyn Accumulator> {
+        Box::new(ExtremumBy::new(Ordering::Greater))
+    }
+
+    pub fn min_by(_aggfunc: &expr::AggregateFunction) -> Box<dyn Accumulator> {
+        Box::new(ExtremumBy::new(Ordering::Less))
+    }
+
+    pub fn every(_aggfunc: &expr::AggregateFunction) -> Box<dyn Accumulator> {
+        Box::new(BoolAgg::new(false))
+    }
+
+    pub fn any_value(_aggfunc: &expr::AggregateFunction) -> Box<dyn Accumulator> {
+        Box::new(AnyValue(ScalarValue::Null))
+    }
+}
+
+/// Evaluate the arguments of an aggregate function for a row.
+///
+/// Aggregates whose result depends on the order of the rows also get the
+/// values of their `ORDER BY` expressions, after the arguments. For
+/// ordered-set aggregates (`WITHIN GROUP`) that's what they aggregate.
+pub fn evaluate_args<'p>(
+    aggfunc: &expr::AggregateFunction,
+    row: &Bound<'p, PyDict>,
+    ctx: &ExecutionContext<'p>,
+) -> Vec<ScalarValue> {
+    let order_by = match is_order_sensitive(aggfunc) {
+        true => aggfunc.order_by.as_deref().unwrap_or_default(),
+        false => &[],
+    };
+    aggfunc
+        .args
+        .iter()
+        .chain(order_by.iter().map(|e| match e {
+            Expr::Sort(sort) => sort.expr.as_ref(),
+            e => e,
+        }))
+        .map(|arg| match arg {
+            // COUNT(*) counts rows, any non-NULL value does
+            Expr::Wildcard { .. } => ScalarValue::Boolean(Some(true)),
+            arg => evaluate_expr(arg, row, ctx),
+        })
+        .collect()
+}
+
+fn is_order_sensitive(aggfunc: &expr::AggregateFunction) -> bool {
+    match &aggfunc.func_def {
+        AggregateFunctionDefinition::BuiltIn(fun) => {
+            matches!(
+                fun,
+                AggregateFunction::ArrayAgg | AggregateFunction::StringAgg
+            )
+        }
+        AggregateFunctionDefinition::UDF(udf) => {
+            find_aggregate(udf.name()).is_some_and(|a| a.is_order_sensitive())
+        }
+        AggregateFunctionDefinition::Name(_) => false,
+    }
+}
+
+fn has_null(values: &[ScalarValue]) -> bool {
+    values.iter().any(|v| v.is_null())
+}
+
+/// `SUM`, NULL until a non-NULL value is added
+struct Sum {
+    sum: ScalarValue,
+    /// Type of the result, derived from the type of the argument
+    data_type: DataType,
+    /// The sum doesn't fit its type, later rows are ignored
+    overflowed: bool,
+}
+
+impl Sum {
+    fn new(arg_type: &DataType) -> Self {
+        let data_type = match arg_type {
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => DataType::Int64,
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                DataType::UInt64
+            }
+            DataType::Float16 | DataType::Float32 | DataType::Float64 => DataType::Float64,
+            // same as DataFusion: 10 more digits, up to the maximum precision
+            DataType::Decimal128(precision, scale) => {
+                DataType::Decimal128(DECIMAL128_MAX_PRECISION.min(precision + 10), *scale)
+            }
+            _ => panic!("SUM of {} is not supported", arg_type),
+        };
+        Self {
+            sum: ScalarValue::Null,
+            data_type,
+            overflowed: false,
+        }
+    }
+}
+
+impl Accumulator for Sum {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if has_null(values) || self.overflowed {
+            return;
+        }
+        // values of a column don't need to share the type of its first row,
+        // the sum widens to the type of a float or of a decimal with more digits
+        self.data_type = numeric_supertype(&self.data_type, &values[0].data_type());
+        let value = values[0].cast_to(&self.data_type).unwrap_or_else(|_| {
+            panic!(
+                "SUM of {} as {} is not supported",
+                values[0], self.data_type
+            )
+        });
+        if self.sum.is_null() {
+            self.sum = value;
+            return;
+        }
+        let (sum, value) = promote_numeric(&self.sum, &value);
+        match sum.add_checked(&value) {
+            Ok(sum) => self.sum = sum,
+            Err(_) => self.overflowed = true,
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        let other = other.downcast_ref::<Self>();
+        self.overflowed |= other.overflowed;
+        self.update(slice::from_ref(&other.sum));
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        self.sum.clone()
+    }
+
+    fn error(&self) -> Option<SqcError> {
+        self.overflowed.then(|| SqcError::Overflow {
+            function: "SUM".to_string(),
+            data_type: self.data_type.clone(),
+        })
+    }
+}
+
+/// `COUNT(*)` and `COUNT(expr, ...)`, which skips rows with a NULL argument
+struct Count(i64);
+
+impl Accumulator for Count {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if !has_null(values) {
+            self.0 += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        self.0 += other.downcast_ref::<Self>().0;
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        ScalarValue::from(self.0)
+    }
+}
+
+#[derive(Default)]
+struct Avg {
+    sum: f64,
+    count: usize,
+}
+
+impl Accumulator for Avg {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if !has_null(values) {
+            self.sum += as_f64(&values[0])
+                .unwrap_or_else(|| panic!("AVG of a non-numeric value: {}", values[0]));
+            self.count += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        let other = other.downcast_ref::<Self>();
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        match self.count {
+            0 => ScalarValue::Float64(None),
+            count => ScalarValue::from(self.sum / count as f64),
+        }
+    }
+}
+
+/// `MIN` (`Less`) or `MAX` (`Greater`)
+struct Extremum {
+    value: ScalarValue,
+    ordering: Ordering,
+}
+
+impl Extremum {
+    fn new(ordering: Ordering) -> Self {
+        Self {
+            value: ScalarValue::Null,
+            ordering,
+        }
+    }
+}
+
+impl Accumulator for Extremum {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if has_null(values) {
+            return;
+        }
+        if self.value.is_null() || compare_values(&values[0], &self.value) == self.ordering {
+            self.value = values[0].clone();
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        self.update(slice::from_ref(&other.downcast_ref::<Self>().value));
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        self.value.clone()
+    }
+}
+
+/// `MAX_BY(value, key)` and `MIN_BY`: the value of the first row with the
+/// largest (smallest) key, rows with a NULL key are ignored
+struct ExtremumBy {
+    value: ScalarValue,
+    key: ScalarValue,
+    ordering: Ordering,
+}
+
+impl ExtremumBy {
+    fn new(ordering: Ordering) -> Self {
+        Self {
+            value: ScalarValue::Null,
+            key: ScalarValue::Null,
+            ordering,
+        }
+    }
+}
+
+impl Accumulator for ExtremumBy {
+    fn update(&mut self, values: &[ScalarValue]) {
+        let key = &values[1];
+        if key.is_null() {
+            return;
+        }
+        if self.key.is_null() || compare_values(key, &self.key) == self.ordering {
+            self.value = values[0].clone();
+            self.key = key.clone();
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        let other = other.downcast_ref::<Self>();
+        self.update(&[other.value.clone(), other.key.clone()]);
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        self.value.clone()
+    }
+}
+
+/// `BOOL_OR` and `BOOL_AND`, the value that decides the result is `short_circuit`
+struct BoolAgg {
+    value: Option<bool>,
+    short_circuit: bool,
+}
+
+impl BoolAgg {
+    fn new(short_circuit: bool) -> Self {
+        Self {
+            value: None,
+            short_circuit,
+        }
+    }
+}
+
+impl Accumulator for BoolAgg {
+    fn update(&mut self, values: &[ScalarValue]) {
+        let value = match &values[0] {
+            ScalarValue::Boolean(Some(value)) => *value,
+            value if value.is_null() => return,
+            value => panic!("Boolean aggregate of a non-boolean value: {}", value),
+        };
+        if self.value != Some(self.short_circuit) {
+            self.value = Some(value);
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        self.update(&[ScalarValue::Boolean(other.downcast_ref::<Self>().value)]);
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        ScalarValue::Boolean(self.value)
+    }
+}
+
+/// `ANY_VALUE`, the first non-NULL value
+struct AnyValue(ScalarValue);
+
+impl Accumulator for AnyValue {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if self.0.is_null() {
+            self.0 = values[0].clone();
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        self.update(slice::from_ref(&other.downcast_ref::<Self>().0));
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        self.0.clone()
+    }
+}
+
+/// `APPROX_DISTINCT`, the HyperLogLog estimate of the number of distinct
+/// non-NULL values
+#[derive(Default)]
+struct ApproxDistinct(HyperLogLog);
+
+impl Accumulator for ApproxDistinct {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if !values[0].is_null() {
+            self.0.add(make_hash(&values[0]));
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        self.0.merge(&other.downcast_ref::<Self>().0);
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        ScalarValue::Int64(Some(self.0.estimate() as i64))
+    }
+}
+
+/// `APPROX_PERCENTILE_CONT(value, fraction [, compression])`, estimated with a
+/// t-digest. The result has the type of the values, like in DataFusion.
+struct ApproxPercentile {
+    digest: TDigest,
+    fraction: Option<f64>,
+    data_type: DataType,
+}
+
+impl ApproxPercentile {
+    fn new(compression: f64, data_type: &DataType) -> Self {
+        Self {
+            digest: TDigest::new(compression),
+            fraction: None,
+            data_type: data_type.clone(),
+        }
+    }
+}
+
+impl Accumulator for ApproxPercentile {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if self.fraction.is_none() {
+            self.fraction = as_f64(&values[1]);
+        }
+        if let Some(value) = as_f64(&values[0]) {
+            self.digest.add(value);
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        let other = other.downcast_ref::<Self>();
+        self.fraction = self.fraction.or(other.fraction);
+        self.digest.merge(&other.digest);
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        let estimate = self.fraction.and_then(|q| self.digest.quantile(q));
+        ScalarValue::Float64(estimate)
+            .cast_to(&self.data_type)
+            .unwrap()
+    }
+}
+
+/// `DISTINCT` aggregates: only the first occurrence of each argument values
+/// reaches the inner accumulator
+struct Distinct {
+    seen: HashSet<Vec<ScalarValue>>,
+    /// The values of `seen` in the order they were added
+    distinct: Vec<Vec<ScalarValue>>,
+    accumulator: Box<dyn Accumulator>,
+}
+
+impl Accumulator for Distinct {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if !has_null(values) && self.seen.insert(values.to_vec()) {
+            self.distinct.push(values.to_vec());
+            self.accumulator.update(values);
+        }
+    }
+
+    /// Values of both might be the same, so the inner states can't be merged
+    fn merge(&mut self, other: &dyn Accumulator) {
+        for values in &other.downcast_ref::<Self>().distinct {
+            self.update(values);
+        }
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        self.accumulator.evaluate()
+    }
+
+    fn error(&self) -> Option<SqcError> {
+        self.accumulator.error()
+    }
+}
+
+/// `VARIANCE` and `STDDEV`, updated with Welford's algorithm
+struct Variance {
+    count: usize,
+    mean: f64,
+    /// Sum of squared differences from the mean
+    m2: f64,
+    sample: bool,
+    sqrt: bool,
+}
+
+impl Variance {
+    fn new(sample: bool, sqrt: bool) -> Self {
+        Self {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            sample,
+            sqrt,
+        }
+    }
+}
+
+impl Accumulator for Variance {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if has_null(values) {
+            return;
+        }
+        let value = as_f64(&values[0])
+            .unwrap_or_else(|| panic!("Variance of a non-numeric value: {}", values[0]));
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Chan's formula for the union of two sets of values
+    fn merge(&mut self, other: &dyn Accumulator) {
+        let other = other.downcast_ref::<Self>();
+        if other.count == 0 {
+            return;
+        }
+        let (n_a, n_b) = (self.count as f64, other.count as f64);
+        let n = n_a + n_b;
+        let delta = other.mean - self.mean;
+        self.count += other.count;
+        self.mean += delta * n_b / n;
+        self.m2 += other.m2 + delta * delta * n_a * n_b / n;
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        let variance = match (self.sample, self.count) {
+            (_, 0) | (true, 1) => return ScalarValue::Float64(None),
+            (true, count) => self.m2 / (count - 1) as f64,
+            (false, count) => self.m2 / count as f64,
+        };
+        ScalarValue::from(if self.sqrt { variance.sqrt() } else { variance })
+    }
+}
+
+/// `PERCENTILE_CONT` (interpolated) and `PERCENTILE_DISC` (an actual value)
+/// over the values in `WITHIN GROUP` order, `MEDIAN` is the continuous 0.5
+/// percentile
+struct Percentile {
+    /// Fixed for `MEDIAN`, otherwise read from the first argument
+    fraction: Option<f64>,
+    values: Vec<ScalarValue>,
+    continuous: bool,
+    asc: bool,
+}
+
+impl Percentile {
+    fn new(fraction: Option<f64>, continuous: bool, asc: bool) -> Self {
+        Self {
+            fraction,
+            values: vec![],
+            continuous,
+            asc,
+        }
+    }
+}
+
+impl Accumulator for Percentile {
+    fn update(&mut self, values: &[ScalarValue]) {
+        let value = match values {
+            [value] => value,
+            [fraction, value] => {
+                if self.fraction.is_none() {
+                    let valid = as_f64(fraction).filter(|f| (0.0..=1.0).contains(f));
+                    self.fraction = Some(valid.unwrap_or_else(|| {
+                        panic!("Percentile must be between 0 and 1, got {}", fraction)
+                    }));
+                }
+                value
+            }
+            _ => panic!("Percentile of {} values", values.len()),
+        };
+        if !value.is_null() {
+            self.values.push(value.clone());
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        let other = other.downcast_ref::<Self>();
+        self.fraction = self.fraction.or(other.fraction);
+        self.values.extend_from_slice(&other.values);
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        if self.values.is_empty() {
+            return ScalarValue::Null;
+        }
+        let mut values = self.values.clone();
+        values.sort_by(|l, r| match self.asc {
+            true => compare_values(l, r),
+            false => compare_values(r, l),
+        });
+        let fraction = self.fraction.unwrap();
+
+        if !self.continuous {
+            // first value whose cumulative distribution reaches the fraction
+            let position = (fraction * values.len() as f64).ceil() as usize;
+            return values[position.max(1) - 1].clone();
+        }
+
+        let position = fraction * (values.len() - 1) as f64;
+        let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+        let as_f64 = |value: &ScalarValue| {
+            as_f64(value).unwrap_or_else(|| panic!("Percentile of a non-numeric value: {}", value))
+        };
+        let (lower_value, upper_value) = (as_f64(&values[lower]), as_f64(&values[upper]));
+        ScalarValue::from(lower_value + (upper_value - lower_value) * (position - lower as f64))
+    }
+}
+
+enum CovarianceKind {
+    Sample,
+    Population,
+    Correlation,
+}
+
+/// `COVAR_SAMP`, `COVAR_POP` and `CORR` of two arguments, updated online
+/// like `Variance`
+struct Covariance {
+    count: usize,
+    mean_x: f64,
+    mean_y: f64,
+    /// Sum of products of the differences from the means
+    c: f64,
+    m2_x: f64,
+    m2_y: f64,
+    kind: CovarianceKind,
+}
+
+impl Covariance {
+    fn new(kind: CovarianceKind) -> Self {
+        Self {
+            count: 0,
+            mean_x: 0.0,
+            mean_y: 0.0,
+            c: 0.0,
+            m2_x: 0.0,
+            m2_y: 0.0,
+            kind,
+        }
+    }
+}
+
+impl Accumulator for Covariance {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if has_null(values) {
+            return;
+        }
+        let as_f64 = |value: &ScalarValue| {
+            as_f64(value).unwrap_or_else(|| panic!("Covariance of a non-numeric value: {}", value))
+        };
+        let (x, y) = (as_f64(&values[0]), as_f64(&values[1]));
+        self.count += 1;
+        let n = self.count as f64;
+        let delta_x = x - self.mean_x;
+        self.mean_x += delta_x / n;
+        let delta_y = y - self.mean_y;
+        self.mean_y += delta_y / n;
+        self.c += delta_x * (y - self.mean_y);
+        self.m2_x += delta_x * (x - self.mean_x);
+        self.m2_y += delta_y * (y - self.mean_y);
+    }
+
+    /// Like `Variance::merge`, with the products of both differences
+    fn merge(&mut self, other: &dyn Accumulator) {
+        let other = other.downcast_ref::<Self>();
+        if other.count == 0 {
+            return;
+        }
+        let (n_a, n_b) = (self.count as f64, other.count as f64);
+        let n = n_a + n_b;
+        let (delta_x, delta_y) = (other.mean_x - self.mean_x, other.mean_y - self.mean_y);
+        self.count += other.count;
+        self.mean_x += delta_x * n_b / n;
+        self.mean_y += delta_y * n_b / n;
+        self.c += other.c + delta_x * delta_y * n_a * n_b / n;
+        self.m2_x += other.m2_x + delta_x * delta_x * n_a * n_b / n;
+        self.m2_y += other.m2_y + delta_y * delta_y * n_a * n_b / n;
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        let result = match (&self.kind, self.count) {
+            (_, 0) | (CovarianceKind::Sample, 1) => None,
+            (CovarianceKind::Sample, count) => Some(self.c / (count - 1) as f64),
+            (CovarianceKind::Population, count) => Some(self.c / count as f64),
+            // undefined when either argument doesn't vary
+            (CovarianceKind::Correlation, _) if self.m2_x == 0.0 || self.m2_y == 0.0 => None,
+            (CovarianceKind::Correlation, _) => Some(self.c / (self.m2_x * self.m2_y).sqrt()),
+        };
+        ScalarValue::Float64(result)
+    }
+}
+
+/// Argument values of the rows of a group, for aggregates that collect them
+/// in the `ORDER BY` order of the aggregate (input order without one)
+struct OrderedRows {
+    order_by: Vec<expr::Sort>,
+    /// Number of arguments, the `ORDER BY` values follow them
+    args: usize,
+    rows: Vec<Vec<ScalarValue>>,
+}
+
+impl OrderedRows {
+    fn new(aggfunc: &expr::AggregateFunction) -> Self {
+        let order_by = aggfunc.order_by.as_deref().unwrap_or_default();
+        Self {
+            order_by: as_sort_exprs(order_by).into_iter().cloned().collect(),
+            args: aggfunc.args.len(),
+            rows: vec![],
+        }
+    }
+
+    fn push(&mut self, values: &[ScalarValue]) {
+        self.rows.push(values.to_vec());
+    }
+
+    /// Rows of `other` come after the rows of this group, which is their
+    /// order without `ORDER BY`
+    fn merge(&mut self, other: &OrderedRows) {
+        self.rows.extend_from_slice(&other.rows);
+    }
+
+    /// Arguments of the rows, sorted
+    fn sorted(&self) -> Vec<&[ScalarValue]> {
+        let order_by: Vec<_> = self.order_by.iter().collect();
+        let mut rows: Vec<_> = self
+            .rows
+            .iter()
+            .map(|row| row.split_at(self.args))
+            .collect();
+        rows.sort_by(|(_, left), (_, right)| compare_keys(&order_by, left, right));
+        rows.into_iter().map(|(args, _)| args).collect()
+    }
+}
+
+/// `ARRAY_AGG`, a list of all values including NULLs
+struct ArrayAgg(OrderedRows);
+
+impl Accumulator for ArrayAgg {
+    fn update(&mut self, values: &[ScalarValue]) {
+        self.0.push(values);
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        self.0.merge(&other.downcast_ref::<Self>().0);
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        let values: Vec<_> = self
+            .0
+            .sorted()
+            .into_iter()
+            .map(|args| args[0].clone())
+            .collect();
+        if values.is_empty() {
+            return ScalarValue::Null;
+        }
+        list_scalar(values)
+    }
+}
+
+/// `STRING_AGG(value, delimiter)`, the delimiter of each row but the first
+/// comes before its value
+struct StringAgg(OrderedRows);
+
+impl Accumulator for StringAgg {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if !values[0].is_null() {
+            self.0.push(values);
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        self.0.merge(&other.downcast_ref::<Self>().0);
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        let rows = self.0.sorted();
+        if rows.is_empty() {
+            return ScalarValue::Utf8(None);
+        }
+        let as_str = |value: &ScalarValue| match value {
+            ScalarValue::Utf8(Some(value)) | ScalarValue::LargeUtf8(Some(value)) => value.clone(),
+            value if value.is_null() => String::new(),
+            value => panic!("STRING_AGG of a non-string value: {}", value),
+        };
+        let mut result = as_str(&rows[0][0]);
+        for args in &rows[1..] {
+            result += &as_str(&args[1]);
+            result += &as_str(&args[0]);
+        }
+        ScalarValue::from(result)
+    }
+}
+
+/// `JSON_OBJECT_AGG(key, value)`, a struct with a field per key, which
+/// becomes a `dict`. The last value of a key wins.
+struct JsonObjectAgg(OrderedRows);
+
+impl Accumulator for JsonObjectAgg {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if values[0].is_null() {
+            panic!("JSON_OBJECT_AGG key can't be NULL");
+        }
+        self.0.push(values);
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        self.0.merge(&other.downcast_ref::<Self>().0);
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        let rows = self.0.sorted();
+        if rows.is_empty() {
+            return ScalarValue::Null;
+        }
+        let mut fields: Vec<(String, ScalarValue)> = vec![];
+        for args in rows {
+            let key = match &args[0] {
+                ScalarValue::Utf8(Some(key)) => key.clone(),
+                key => key.to_string(),
+            };
+            match fields.iter_mut().find(|(name, _)| *name == key) {
+                Some((_, value)) => *value = args[1].clone(),
+                None => fields.push((key, args[1].clone())),
+            }
+        }
+        struct_scalar(fields)
+    }
+}
@@ -1,49 +1,230 @@
-use std::collections::BTreeMap;
+use std::collections::HashMap;
 
-use datafusion_expr::Aggregate;
-use pyo3::{
-    types::{IntoPyDict, PyDict},
-    Bound,
+use datafusion_common::ScalarValue;
+use datafusion_expr::{
+    expr::{AggregateFunctionDefinition, Alias},
+    Aggregate, AggregateFunction, Expr, GroupingSet,
 };
+use pyo3::types::{PyDict, PyDictMethods};
 
 use super::{
-    common::{evaluate_agg_expr, evaluate_expr, make_hash, scalar_to_py_any},
-    ExecutionContext,
+    accumulator::{create_accumulator, evaluate_args, with_filter, Accumulator},
+    common::{evaluate_expr, scalar_to_py_any},
+    ExecutionContext, Rows,
 };
 
-pub fn execute<'p>(aggregate: &Aggregate, ctx: &ExecutionContext<'p>) -> Vec<Bound<'p, PyDict>> {
-    let input = super::execute_plan(&aggregate.input, ctx);
-    let (keys, groups): (BTreeMap<u64, Vec<_>>, BTreeMap<u64, Vec<_>>) =
-        input
-            .into_iter()
-            .fold((BTreeMap::new(), BTreeMap::new()), |mut acc, row| {
-                let key: Vec<_> = aggregate
-                    .group_expr
-                    .iter()
-                    .map(|e| evaluate_expr(e, &row))
-                    .collect();
-
-                let key_hash = make_hash(&key);
-                acc.0.entry(key_hash).or_insert(key);
-                acc.1.entry(key_hash).or_default().push(row);
-                acc
-            });
-
-    keys.into_iter()
-        .map(|(key_hash, key_values)| {
-            let group = groups.get(&key_hash).unwrap();
-            let values = aggregate
-                .aggr_expr
-                .iter()
-                .map(|e| evaluate_agg_expr(e, group));
-
-            aggregate
-                .schema
-                .iter()
-                .map(|(_, field)| field.name())
-                .zip(key_values.into_iter().chain(values))
-                .map(|(name, value)| (name, scalar_to_py_any(ctx.py, &value)))
-                .into_py_dict_bound(ctx.py)
+/// One accumulator per aggregate expression of a group
+type Accumulators = Vec<Box<dyn Accumulator>>;
+
+pub fn execute<'a, 'p>(aggregate: &'a Aggregate, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
+    let (group_exprs, grouping_sets) = grouping_sets(&aggregate.group_expr);
+    let sources = merge_sources(&grouping_sets);
+    let aggfuncs: Vec<_> = aggregate
+        .aggr_expr
+        .iter()
+        .map(|e| match e {
+            Expr::AggregateFunction(aggfunc) => with_filter(aggfunc),
+            Expr::Alias(Alias { expr, .. }) => match expr.as_ref() {
+                Expr::AggregateFunction(aggfunc) => with_filter(aggfunc),
+                _ => panic!("Unsupported aggregate expression: {:?}", e),
+            },
+            _ => panic!("Unsupported aggregate expression: {:?}", e),
+        })
+        .collect();
+
+    // groups in first-seen order, each with its grouping set, its key and one
+    // accumulator per aggregate
+    let mut positions: HashMap<(usize, Vec<ScalarValue>), usize> = HashMap::new();
+    let mut groups: Vec<(usize, Vec<ScalarValue>, Accumulators)> = vec![];
+    let new_group = |set: usize| -> Accumulators {
+        aggfuncs
+            .iter()
+            .map(|aggfunc| match aggfunc.func_def {
+                AggregateFunctionDefinition::BuiltIn(AggregateFunction::Grouping) => {
+                    let grouping =
+                        evaluate_grouping(&aggfunc.args, &group_exprs, &grouping_sets[set]);
+                    Box::new(Grouping(grouping)) as Box<dyn Accumulator>
+                }
+                _ => create_accumulator(aggfunc, aggregate.input.schema()),
+            })
+            .collect()
+    };
+
+    for row in super::execute_plan(&aggregate.input, ctx) {
+        let values: Vec<_> = group_exprs
+            .iter()
+            .map(|e| evaluate_expr(e, &row, ctx))
+            .collect();
+        // arguments of the aggregates whose FILTER the row passes
+        let args: Vec<_> = aggfuncs
+            .iter()
+            .map(|aggfunc| match &aggfunc.filter {
+                Some(filter)
+                    if !matches!(
+                        evaluate_expr(filter, &row, ctx),
+                        ScalarValue::Boolean(Some(true))
+                    ) =>
+                {
+                    None
+                }
+                _ => Some(evaluate_args(aggfunc, &row, ctx)),
+            })
+            .collect();
+
+        // every row belongs to one group per grouping set, the index of the set
+        // is part of the key so subtotals don't mix with groups of NULL values
+        for (set, included) in grouping_sets.iter().enumerate() {
+            let key = set_key(&values, included);
+            let position = match positions.get(&(set, key.clone())) {
+                Some(&position) => position,
+                None => {
+                    groups.push((set, key.clone(), new_group(set)));
+                    positions.insert((set, key), groups.len() - 1);
+                    groups.len() - 1
+                }
+            };
+            // groups of the other sets are added in first-seen order too, but
+            // their rows come from the merge below
+            if sources[set].is_some() {
+                continue;
+            }
+            for (accumulator, args) in groups[position].2.iter_mut().zip(&args) {
+                if let Some(args) = args {
+                    accumulator.update(args);
+                }
+            }
+        }
+    }
+
+    // a group of a coarser set is the union of the groups of a finer set
+    // with the same values for its expressions, e.g. a subtotal of a ROLLUP
+    for position in 0..groups.len() {
+        let source = groups[position].0;
+        for set in (0..sources.len()).filter(|&set| sources[set] == Some(source)) {
+            let key = set_key(&groups[position].1, &grouping_sets[set]);
+            let target = positions[&(set, key)];
+            let mut accumulators = std::mem::take(&mut groups[target].2);
+            for (accumulator, other) in accumulators.iter_mut().zip(&groups[position].2) {
+                accumulator.merge(other.as_ref());
+            }
+            groups[target].2 = accumulators;
+        }
+    }
+
+    // the grand total (a global aggregate or the empty grouping set)
+    // has a row even without input rows
+    for (set, included) in grouping_sets.iter().enumerate() {
+        let key = vec![ScalarValue::Null; group_exprs.len()];
+        if !included.contains(&true) && !positions.contains_key(&(set, key.clone())) {
+            groups.push((set, key, new_group(set)));
+        }
+    }
+
+    let columns = aggregate.schema.columns();
+    Box::new(groups.into_iter().map_while(move |(_, key, accumulators)| {
+        // an aggregate without a result, e.g. an overflowed SUM, fails the query
+        if let Some(error) = accumulators.iter().find_map(|a| a.error()) {
+            ctx.fail(error);
+            return None;
+        }
+        let values = accumulators.iter().map(|a| a.evaluate());
+
+        // group columns are also stored under their qualified name, like join rows,
+        // so HAVING and the projection above can tell apart keys of different tables
+        let result = PyDict::new_bound(ctx.py);
+        for (column, value) in columns.iter().zip(key.into_iter().chain(values)) {
+            let value = scalar_to_py_any(ctx.py, &value);
+            if column.relation.is_some() {
+                result.set_item(column.flat_name(), &value).unwrap();
+            }
+            result.set_item(&column.name, value).unwrap();
+        }
+        Some(result)
+    }))
+}
+
+/// Group expressions and the grouping sets over them, as flags telling whether
+/// each expression is part of the set.
+///
+/// A plain `GROUP BY` is a single set of all expressions.
+fn grouping_sets(group_expr: &[Expr]) -> (Vec<Expr>, Vec<Vec<bool>>) {
+    let grouping_set = match group_expr {
+        [Expr::GroupingSet(grouping_set)] => grouping_set,
+        _ => return (group_expr.to_vec(), vec![vec![true; group_expr.len()]]),
+    };
+
+    let exprs = grouping_set.distinct_expr();
+    let n = exprs.len();
+    let sets = match grouping_set {
+        // (a, b, c), (a, b), (a), ()
+        GroupingSet::Rollup(_) => (0..=n)
+            .rev()
+            .map(|len| (0..n).map(|i| i < len).collect())
+            .collect(),
+        // every subset, starting from the full one
+        GroupingSet::Cube(_) => (0..1usize << n)
+            .rev()
+            .map(|mask| (0..n).map(|i| mask & (1 << (n - 1 - i)) != 0).collect())
+            .collect(),
+        GroupingSet::GroupingSets(sets) => sets
+            .iter()
+            .map(|set| exprs.iter().map(|e| set.contains(e)).collect())
+            .collect(),
+    };
+    (exprs, sets)
+}
+
+/// Values of the group expressions in a grouping set, NULL for the others
+fn set_key(values: &[ScalarValue], included: &[bool]) -> Vec<ScalarValue> {
+    values
+        .iter()
+        .zip(included)
+        .map(|(value, &included)| match included {
+            true => value.clone(),
+            false => ScalarValue::Null,
+        })
+        .collect()
+}
+
+/// For each grouping set, the set whose groups are merged into its groups,
+/// `None` for the sets updated from the rows.
+///
+/// Those are the sets that no other set contains (the first of equal sets),
+/// any other set is contained in one of them.
+fn merge_sources(sets: &[Vec<bool>]) -> Vec<Option<usize>> {
+    let contains = |i: usize, j: usize| sets[i].iter().zip(&sets[j]).all(|(&a, &b)| a || !b);
+    let is_source =
+        |i: usize| !(0..sets.len()).any(|j| contains(j, i) && (!contains(i, j) || j < i));
+    (0..sets.len())
+        .map(|i| match is_source(i) {
+            true => None,
+            false => (0..sets.len()).find(|&j| is_source(j) && contains(j, i)),
         })
         .collect()
 }
+
+/// `GROUPING(a, b, ...)`: a bit per argument, the first one being the most
+/// significant, set when the argument is rolled up (not part of the grouping set)
+fn evaluate_grouping(args: &[Expr], group_exprs: &[Expr], included: &[bool]) -> ScalarValue {
+    let grouping = args.iter().fold(0, |acc, arg| {
+        let position = group_exprs
+            .iter()
+            .position(|e| e == arg)
+            .unwrap_or_else(|| panic!("GROUPING argument {} is not a group expression", arg));
+        (acc << 1) | !included[position] as i64
+    });
+    ScalarValue::from(grouping)
+}
+
+/// `GROUPING()` only depends on the grouping set of the group
+struct Grouping(ScalarValue);
+
+impl Accumulator for Grouping {
+    fn update(&mut self, _values: &[ScalarValue]) {}
+
+    fn merge(&mut self, _other: &dyn Accumulator) {}
+
+    fn evaluate(&self) -> ScalarValue {
+        self.0.clone()
+    }
+}
@@ -1,70 +1,309 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
 
-use datafusion_common::{arrow::datatypes::DataType, ScalarValue};
+use datafusion_common::{
+    arrow::{
+        array::StructArray,
+        datatypes::{DataType, Field, DECIMAL128_MAX_PRECISION},
+    },
+    Column, ScalarValue,
+};
 use datafusion_expr::{
     expr::AggregateFunctionDefinition, AggregateFunction, ColumnarValue, Expr, Operator,
 };
 use pyo3::{
-    types::{PyAnyMethods, PyDict, PyDictMethods, PyNone},
+    types::{PyAnyMethods, PyDateTime, PyDict, PyDictMethods, PyList, PyListMethods, PyNone},
     Bound, Py, PyAny, Python, ToPyObject,
 };
 
+use super::{subquery, ExecutionContext};
+
+/// Milliseconds in a day, the unit of `Date64` values
+pub const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Proleptic Gregorian ordinal of 1970-01-01, as returned by `date.toordinal()`
+const EPOCH_ORDINAL: i64 = 719_163;
+
 pub fn make_hash<T: Hash>(value: T) -> u64 {
     let mut hasher = DefaultHasher::new();
     value.hash(&mut hasher);
     hasher.finish()
 }
 
+/// Numeric value as `f64`, used to compare values of different numeric types.
+pub fn as_f64(value: &ScalarValue) -> Option<f64> {
+    match value {
+        ScalarValue::Int8(Some(v)) => Some(*v as f64),
+        ScalarValue::Int16(Some(v)) => Some(*v as f64),
+        ScalarValue::Int32(Some(v)) => Some(*v as f64),
+        ScalarValue::Int64(Some(v)) => Some(*v as f64),
+        ScalarValue::UInt8(Some(v)) => Some(*v as f64),
+        ScalarValue::UInt16(Some(v)) => Some(*v as f64),
+        ScalarValue::UInt32(Some(v)) => Some(*v as f64),
+        ScalarValue::UInt64(Some(v)) => Some(*v as f64),
+        ScalarValue::Float32(Some(v)) => Some(*v as f64),
+        ScalarValue::Float64(Some(v)) => Some(*v),
+        ScalarValue::Decimal128(Some(v), _, scale) => Some(*v as f64 / 10f64.powi(*scale as i32)),
+        _ => None,
+    }
+}
+
+/// Total order of non-null values as used by `ORDER BY`.
+///
+/// Values of different numeric types (e.g. `Int64` and `Float64`) are compared
+/// numerically. Placement of NULLs is up to the caller.
+pub fn compare_values(left: &ScalarValue, right: &ScalarValue) -> Ordering {
+    if let Some(ordering) = left.partial_cmp(right) {
+        return ordering;
+    }
+
+    match (as_f64(left), as_f64(right)) {
+        (Some(l), Some(r)) => l.total_cmp(&r),
+        _ => panic!("Cannot compare {:?} with {:?}", left, right),
+    }
+}
+
+/// Equality of non-null values, values of different numeric types (e.g.
+/// `Int64` and `Float64`) are compared numerically
+pub fn values_equal(left: &ScalarValue, right: &ScalarValue) -> bool {
+    left == right || matches!((as_f64(left), as_f64(right)), (Some(l), Some(r)) if l == r)
+}
+
+/// Type that values of both numeric types convert to without losing their
+/// value: `Float64` if either is a float, else a decimal with the larger
+/// scale if either is a decimal, else a 64-bit integer.
+pub fn numeric_supertype(left: &DataType, right: &DataType) -> DataType {
+    match (left, right) {
+        _ if left == right => left.clone(),
+        (DataType::Float16 | DataType::Float32 | DataType::Float64, _)
+        | (_, DataType::Float16 | DataType::Float32 | DataType::Float64) => DataType::Float64,
+        (DataType::Decimal128(..), _) | (_, DataType::Decimal128(..)) => {
+            let (left_digits, left_scale) = decimal_digits(left);
+            let (right_digits, right_scale) = decimal_digits(right);
+            let scale = left_scale.max(right_scale);
+            let precision = left_digits.max(right_digits) + scale as u8;
+            DataType::Decimal128(DECIMAL128_MAX_PRECISION.min(precision), scale)
+        }
+        _ if left.is_unsigned_integer() && right.is_unsigned_integer() => DataType::UInt64,
+        _ => DataType::Int64,
+    }
+}
+
+/// Digits before the decimal point and scale of a decimal or integer type
+fn decimal_digits(data_type: &DataType) -> (u8, i8) {
+    match data_type {
+        DataType::Decimal128(precision, scale) => (precision - (*scale).max(0) as u8, *scale),
+        // u64::MAX has 20 digits
+        _ => (20, 0),
+    }
+}
+
+/// Both numeric values, converted to their `numeric_supertype` so they can be
+/// added or subtracted
+pub fn promote_numeric(left: &ScalarValue, right: &ScalarValue) -> (ScalarValue, ScalarValue) {
+    let data_type = numeric_supertype(&left.data_type(), &right.data_type());
+    (
+        cast_numeric(left, &data_type),
+        cast_numeric(right, &data_type),
+    )
+}
+
+/// A numeric value converted to a `numeric_supertype` of its type
+pub fn cast_numeric(value: &ScalarValue, data_type: &DataType) -> ScalarValue {
+    value
+        .cast_to(data_type)
+        .unwrap_or_else(|_| panic!("Cannot convert {:?} to {}", value, data_type))
+}
+
 pub fn scalar_to_py_any(py: Python, value: &ScalarValue) -> Py<PyAny> {
     match value {
-        ScalarValue::Int64(Some(v)) => v.to_object(py).into(),
-        ScalarValue::Float64(Some(v)) => v.to_object(py).into(),
-        ScalarValue::Utf8(Some(v)) => v.to_object(py).into(),
-        ScalarValue::Boolean(Some(v)) => v.to_object(py).into(),
-        v if v.is_null() => PyNone::get_bound(py).to_object(py).into(),
+        ScalarValue::Int64(Some(v)) => v.to_object(py),
+        ScalarValue::Float64(Some(v)) => v.to_object(py),
+        ScalarValue::Utf8(Some(v)) => v.to_object(py),
+        ScalarValue::Boolean(Some(v)) => v.to_object(py),
+        ScalarValue::Decimal128(Some(v), _, scale) => py
+            .import_bound("decimal")
+            .unwrap()
+            .getattr("Decimal")
+            .unwrap()
+            .call1((format!("{}e-{}", v, scale),))
+            .unwrap()
+            .unbind(),
+        ScalarValue::Date64(Some(v)) => py
+            .import_bound("datetime")
+            .unwrap()
+            .getattr("date")
+            .unwrap()
+            .call_method1(
+                "fromordinal",
+                (v.div_euclid(MILLIS_PER_DAY) + EPOCH_ORDINAL,),
+            )
+            .unwrap()
+            .unbind(),
+        v if v.is_null() => PyNone::get_bound(py).to_object(py),
+        ScalarValue::List(list) => {
+            let values = list.value(0);
+            let values: Vec<_> = (0..values.len())
+                .map(|i| scalar_to_py_any(py, &ScalarValue::try_from_array(&values, i).unwrap()))
+                .collect();
+            PyList::new_bound(py, values).unbind().into_any()
+        }
+        ScalarValue::Struct(fields) => {
+            let result = PyDict::new_bound(py);
+            for (field, column) in fields.fields().iter().zip(fields.columns()) {
+                let value = ScalarValue::try_from_array(column, 0).unwrap();
+                result
+                    .set_item(field.name(), scalar_to_py_any(py, &value))
+                    .unwrap();
+            }
+            result.unbind().into_any()
+        }
         _ => unimplemented!("Value {}", value),
     }
 }
 
-pub fn evaluate_agg_expr<'p>(expr: &Expr, rows: &Vec<Bound<'p, PyDict>>) -> ScalarValue {
-    match expr {
-        Expr::AggregateFunction(aggfunc) => match aggfunc.func_def {
-            AggregateFunctionDefinition::BuiltIn(AggregateFunction::Sum) => {
-                let expr = aggfunc.args.first().unwrap();
-                let mut result = ScalarValue::new_zero(&DataType::Int64).unwrap();
-                for row in rows {
-                    result = result.add(evaluate_expr(expr, row)).unwrap();
-                }
-                result
+/// Look up a column in a row.
+///
+/// Rows produced by joins also carry qualified keys (`table.column`) so that
+/// columns with the same name on both sides can be told apart; rows coming
+/// straight from a table or a projection only have unqualified keys.
+pub fn get_column<'p>(column: &Column, row: &Bound<'p, PyDict>) -> Option<Bound<'p, PyAny>> {
+    if column.relation.is_some() {
+        if let Some(value) = row.get_item(column.flat_name()).unwrap() {
+            return Some(value);
+        }
+    }
+    row.get_item(&column.name).unwrap()
+}
+
+pub fn py_any_to_scalar(value: &Bound<'_, PyAny>) -> ScalarValue {
+    let data_type = crate::infer_field_schema(value);
+    match data_type {
+        DataType::Null => ScalarValue::Null,
+        DataType::Boolean => ScalarValue::Boolean(value.extract::<bool>().ok()),
+        DataType::Int64 => ScalarValue::Int64(value.extract::<i64>().ok()),
+        DataType::Float64 => ScalarValue::Float64(value.extract::<f64>().ok()),
+        DataType::Utf8 => ScalarValue::Utf8(value.extract::<String>().ok()),
+        DataType::Decimal128(precision, scale) => {
+            ScalarValue::Decimal128(Some(decimal_to_i128(value, scale)), precision, scale)
+        }
+        DataType::Date64 if value.is_instance_of::<PyDateTime>() => {
+            unimplemented!("datetime values")
+        }
+        DataType::Date64 => {
+            let ordinal = value.call_method0("toordinal").unwrap().extract::<i64>();
+            ScalarValue::Date64(ordinal.ok().map(|v| (v - EPOCH_ORDINAL) * MILLIS_PER_DAY))
+        }
+        DataType::List(_) => {
+            let values = value.downcast::<PyList>().unwrap();
+            list_scalar(values.iter().map(|v| py_any_to_scalar(&v)).collect())
+        }
+        DataType::Struct(_) => {
+            let fields = value.downcast::<PyDict>().unwrap();
+            struct_scalar(
+                fields
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), py_any_to_scalar(&v)))
+                    .collect(),
+            )
+        }
+        _ => unimplemented!(),
+    }
+}
+
+/// A list of values of the type of its non-NULL values, numbers of different
+/// types are converted to their `numeric_supertype`
+pub fn list_scalar(values: Vec<ScalarValue>) -> ScalarValue {
+    let data_type = values
+        .iter()
+        .filter(|v| !v.is_null())
+        .map(|v| v.data_type())
+        .reduce(|left, right| {
+            if left.is_numeric() && right.is_numeric() {
+                numeric_supertype(&left, &right)
+            } else {
+                left
             }
-            _ => unimplemented!(),
-        },
-        _ => panic!("Unsupported expression: {:?}", expr),
+        })
+        .unwrap_or(DataType::Null);
+    let null = ScalarValue::try_from(&data_type).unwrap();
+    let values: Vec<_> = values
+        .into_iter()
+        .map(|v| match v {
+            _ if v.is_null() => null.clone(),
+            _ if v.data_type() != data_type => cast_numeric(&v, &data_type),
+            _ => v,
+        })
+        .collect();
+    ScalarValue::List(ScalarValue::new_list(&values, &data_type))
+}
+
+/// A struct of named values, the representation of a `dict`
+pub fn struct_scalar(fields: Vec<(String, ScalarValue)>) -> ScalarValue {
+    let (fields, arrays): (Vec<_>, Vec<_>) = fields
+        .into_iter()
+        .map(|(name, value)| {
+            let field = Field::new(name, value.data_type(), true);
+            (field, value.to_array().unwrap())
+        })
+        .unzip();
+    ScalarValue::Struct(Arc::new(
+        StructArray::try_new(fields.into(), arrays, None).unwrap(),
+    ))
+}
+
+/// Digits of a `decimal.Decimal` as an integer scaled by `10^scale`
+fn decimal_to_i128(value: &Bound<'_, PyAny>, scale: i8) -> i128 {
+    let (sign, digits, exponent): (i64, Vec<i128>, i64) = value
+        .call_method0("as_tuple")
+        .unwrap()
+        .extract()
+        .unwrap_or_else(|_| unimplemented!("Decimal {}", value));
+    let digits = digits
+        .into_iter()
+        .fold(0i128, |acc, digit| acc * 10 + digit);
+    let result = digits * 10i128.pow((exponent + scale as i64) as u32);
+    match sign {
+        0 => result,
+        _ => -result,
     }
 }
 
-pub fn evaluate_expr<'p>(expr: &Expr, row: &Bound<'p, PyDict>) -> ScalarValue {
+pub fn evaluate_column<'p>(column: &Column, row: &Bound<'p, PyDict>) -> ScalarValue {
+    match get_column(column, row) {
+        None => ScalarValue::Null,
+        Some(value) => py_any_to_scalar(&value),
+    }
+}
+
+pub fn evaluate_columns<'p>(columns: &[Column], row: &Bound<'p, PyDict>) -> Vec<ScalarValue> {
+    columns
+        .iter()
+        .map(|column| evaluate_column(column, row))
+        .collect()
+}
+
+pub fn evaluate_expr<'p>(
+    expr: &Expr,
+    row: &Bound<'p, PyDict>,
+    ctx: &ExecutionContext<'p>,
+) -> ScalarValue {
     match expr {
         Expr::Literal(lit) => lit.clone(),
-        Expr::Alias(alias) => evaluate_expr(&alias.expr, row),
-        Expr::Column(column) => match row.get_item(&column.name).unwrap() {
-            None => ScalarValue::Null,
-            Some(value) => {
-                let data_type = crate::infer_field_schema(&value);
-                match data_type {
-                    DataType::Null => ScalarValue::Null,
-                    DataType::Boolean => ScalarValue::Boolean(value.extract::<bool>().ok()),
-                    DataType::Int64 => ScalarValue::Int64(value.extract::<i64>().ok()),
-                    DataType::Float64 => ScalarValue::Float64(value.extract::<f64>().ok()),
-                    DataType::Utf8 => ScalarValue::Utf8(value.extract::<String>().ok()),
-                    _ => unimplemented!(),
-                }
-            }
-        },
+        Expr::Alias(alias) => evaluate_expr(&alias.expr, row, ctx),
+        Expr::Column(column) => evaluate_column(column, row),
         Expr::BinaryExpr(binary_expr) => {
-            let left_val = evaluate_expr(&binary_expr.left, row);
-            let right_val = evaluate_expr(&binary_expr.right, row);
+            let left_val = evaluate_expr(&binary_expr.left, row, ctx);
+            let right_val = evaluate_expr(&binary_expr.right, row, ctx);
             match binary_expr.op {
+                // arithmetic on NULL gives NULL
+                Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide
+                    if left_val.is_null() || right_val.is_null() =>
+                {
+                    ScalarValue::Null
+                }
                 Operator::Plus => left_val.add(right_val).unwrap(),
                 Operator::Minus => left_val.sub(right_val).unwrap(),
                 Operator::Multiply => left_val.mul(right_val).unwrap(),
@@ -90,12 +329,58 @@ pub fn evaluate_expr<'p>(expr: &Expr, row: &Bound<'p, PyDict>) -> ScalarValue {
                 _ => panic!("Unsupported binary expression: {}", binary_expr),
             }
         }
+        Expr::Case(case) => {
+            // the first branch that matches wins, the others aren't evaluated
+            let operand = case.expr.as_ref().map(|e| evaluate_expr(e, row, ctx));
+            for (when, then) in &case.when_then_expr {
+                let when = evaluate_expr(when, row, ctx);
+                let matched = match &operand {
+                    // a NULL operand doesn't equal any value
+                    Some(operand) => {
+                        !operand.is_null() && !when.is_null() && values_equal(operand, &when)
+                    }
+                    None => matches!(when, ScalarValue::Boolean(Some(true))),
+                };
+                if matched {
+                    return evaluate_expr(then, row, ctx);
+                }
+            }
+            match &case.else_expr {
+                Some(else_expr) => evaluate_expr(else_expr, row, ctx),
+                None => ScalarValue::Null,
+            }
+        }
+        Expr::Between(between) => {
+            let value = evaluate_expr(&between.expr, row, ctx);
+            let low = evaluate_expr(&between.low, row, ctx);
+            let high = evaluate_expr(&between.high, row, ctx);
+            if value.is_null() || low.is_null() || high.is_null() {
+                return ScalarValue::Boolean(None);
+            }
+            let is_between =
+                compare_values(&low, &value).is_le() && compare_values(&value, &high).is_le();
+            (is_between != between.negated).into()
+        }
+        Expr::OuterReferenceColumn(_, column) => {
+            // the innermost outer row that has the column
+            let outer_rows = ctx.outer_rows.borrow();
+            outer_rows
+                .iter()
+                .rev()
+                .find_map(|outer_row| get_column(column, outer_row))
+                .map_or(ScalarValue::Null, |value| py_any_to_scalar(&value))
+        }
+        Expr::Exists(exists) => subquery::evaluate_exists(exists, row, ctx),
+        Expr::InSubquery(in_subquery) => subquery::evaluate_in_subquery(in_subquery, row, ctx),
+        Expr::ScalarSubquery(scalar_subquery) => {
+            subquery::evaluate_scalar_subquery(scalar_subquery, row, ctx)
+        }
         Expr::ScalarFunction(scalar_function) => match &scalar_function.func_def {
             datafusion_expr::ScalarFunctionDefinition::UDF(udf) => {
                 let args: Vec<ColumnarValue> = scalar_function
                     .args
                     .iter()
-                    .map(|arg| evaluate_expr(arg, row).into())
+                    .map(|arg| evaluate_expr(arg, row, ctx).into())
                     .collect();
 
                 let udf_result = udf.invoke(&args).unwrap();
@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use datafusion_common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
+use datafusion_expr::{
+    expr::{Exists, InSubquery},
+    utils::{conjunction, split_conjunction_owned},
+    BinaryExpr, Expr, Filter, Join, JoinConstraint, JoinType, LogicalPlan, Operator, Subquery,
+};
+
+/// Replace filters on `[NOT] EXISTS (...)` and `x IN (...)` with semi and
+/// anti joins, like DataFusion's `DecorrelatePredicateSubquery` optimizer rule.
+///
+/// A correlated subquery otherwise runs once per distinct outer value, the
+/// join reads it once and probes a hash table. Only subqueries whose outer
+/// references are in the `WHERE` clause of a plain `SELECT ... FROM ... WHERE`
+/// are rewritten, and only when that gives equi-join keys. `NOT IN` keeps its
+/// per-row evaluation because of its NULL semantics.
+pub fn decorrelate_predicate_subqueries(plan: LogicalPlan) -> LogicalPlan {
+    // bottom up, so the subqueries of a filter are rewritten before it becomes a join
+    plan.transform_up_with_subqueries(|plan| match &plan {
+        LogicalPlan::Filter(filter) => match decorrelate_filter(filter) {
+            Some(join) => Ok(Transformed::yes(join)),
+            None => Ok(Transformed::no(plan)),
+        },
+        _ => Ok(Transformed::no(plan)),
+    })
+    .unwrap()
+    .data
+}
+
+fn decorrelate_filter(filter: &Filter) -> Option<LogicalPlan> {
+    let mut input = filter.input.clone();
+    let mut remaining = vec![];
+    let mut transformed = false;
+    for predicate in split_conjunction_owned(filter.predicate.clone()) {
+        let join = match &predicate {
+            Expr::Exists(Exists { subquery, negated }) => {
+                semi_join(&input, subquery, None, *negated)
+            }
+            Expr::InSubquery(InSubquery {
+                expr,
+                subquery,
+                negated: false,
+            }) => semi_join(&input, subquery, Some(expr), false),
+            _ => None,
+        };
+        match join {
+            Some(join) => {
+                input = Arc::new(LogicalPlan::Join(join));
+                transformed = true;
+            }
+            None => remaining.push(predicate),
+        }
+    }
+
+    if !transformed {
+        return None;
+    }
+    Some(match conjunction(remaining) {
+        Some(predicate) => LogicalPlan::Filter(Filter::try_new(predicate, input).unwrap()),
+        None => Arc::unwrap_or_clone(input),
+    })
+}
+
+/// Semi (anti when `negated`) join of `input` with the rows of `subquery`,
+/// `None` when the subquery can't be turned into a join
+fn semi_join(
+    input: &Arc<LogicalPlan>,
+    subquery: &Subquery,
+    in_expr: Option<&Expr>,
+    negated: bool,
+) -> Option<Join> {
+    let mut plan = subquery.subquery.as_ref();
+
+    // the value of `x IN (SELECT value ...)` is a join key, EXISTS ignores the columns
+    let mut on = vec![];
+    if let LogicalPlan::Projection(projection) = plan {
+        if let Some(expr) = in_expr {
+            match projection.expr.as_slice() {
+                [value] => on.push((expr.clone(), value.clone().unalias())),
+                _ => return None,
+            }
+        }
+        plan = projection.input.as_ref();
+    } else if in_expr.is_some() {
+        return None;
+    }
+
+    // correlated equalities become join keys, other correlated predicates
+    // are checked on the joined rows
+    let mut residual = vec![];
+    let right = match plan {
+        LogicalPlan::Filter(filter) => {
+            let mut inner = vec![];
+            for predicate in split_conjunction_owned(filter.predicate.clone()) {
+                if !has_outer_reference(&predicate) {
+                    inner.push(predicate);
+                    continue;
+                }
+                match predicate {
+                    Expr::BinaryExpr(BinaryExpr {
+                        left,
+                        op: Operator::Eq,
+                        right,
+                    }) if is_outer_only(&left) && !has_outer_reference(&right) => {
+                        on.push((strip_outer_reference(*left), *right))
+                    }
+                    Expr::BinaryExpr(BinaryExpr {
+                        left,
+                        op: Operator::Eq,
+                        right,
+                    }) if is_outer_only(&right) && !has_outer_reference(&left) => {
+                        on.push((strip_outer_reference(*right), *left))
+                    }
+                    predicate => residual.push(strip_outer_reference(predicate)),
+                }
+            }
+            match conjunction(inner) {
+                Some(predicate) => Arc::new(LogicalPlan::Filter(
+                    Filter::try_new(predicate, filter.input.clone()).unwrap(),
+                )),
+                None => filter.input.clone(),
+            }
+        }
+        plan => Arc::new(plan.clone()),
+    };
+
+    // whatever is left must not depend on the outer row
+    if on.is_empty() || on.iter().any(|(_, right)| has_outer_reference(right)) {
+        return None;
+    }
+    if plan_has_outer_reference(&right) {
+        return None;
+    }
+
+    Some(Join {
+        left: input.clone(),
+        right,
+        on,
+        filter: conjunction(residual),
+        join_type: match negated {
+            true => JoinType::LeftAnti,
+            false => JoinType::LeftSemi,
+        },
+        join_constraint: JoinConstraint::On,
+        schema: input.schema().clone(),
+        null_equals_null: false,
+    })
+}
+
+fn has_outer_reference(expr: &Expr) -> bool {
+    expr.exists(|e| Ok(matches!(e, Expr::OuterReferenceColumn(..))))
+        .unwrap()
+}
+
+/// Whether an expression only depends on the outer row
+fn is_outer_only(expr: &Expr) -> bool {
+    has_outer_reference(expr) && !expr.exists(|e| Ok(matches!(e, Expr::Column(_)))).unwrap()
+}
+
+/// Whether a plan refers to an outer row, or has subqueries that might
+fn plan_has_outer_reference(plan: &LogicalPlan) -> bool {
+    let mut found = false;
+    plan.apply(|plan| {
+        plan.apply_expressions(|expr| {
+            found |= has_outer_reference(expr)
+                || expr
+                    .exists(|e| {
+                        Ok(matches!(
+                            e,
+                            Expr::Exists(_) | Expr::InSubquery(_) | Expr::ScalarSubquery(_)
+                        ))
+                    })
+                    .unwrap();
+            Ok(TreeNodeRecursion::Continue)
+        })
+    })
+    .unwrap();
+    found
+}
+
+/// Outer columns become columns of the left side of the join
+fn strip_outer_reference(expr: Expr) -> Expr {
+    expr.transform_up(|e| match e {
+        Expr::OuterReferenceColumn(_, column) => Ok(Transformed::yes(Expr::Column(column))),
+        e => Ok(Transformed::no(e)),
+    })
+    .unwrap()
+    .data
+}
@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+
+use datafusion_common::ScalarValue;
+use datafusion_expr::{Distinct, DistinctOn};
+
+use super::{
+    common::{evaluate_columns, evaluate_expr},
+    projection::project_row,
+    sort::sort_rows,
+    ExecutionContext, Rows,
+};
+
+pub fn execute<'a, 'p>(distinct: &'a Distinct, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
+    match distinct {
+        Distinct::All(input) => {
+            let columns = input.schema().columns();
+            let mut seen = HashSet::new();
+            // HashSet compares the values themselves, not only their hashes
+            Box::new(
+                super::execute_plan(input, ctx)
+                    .filter(move |row| seen.insert(evaluate_columns(&columns, row))),
+            )
+        }
+        Distinct::On(distinct_on) => execute_distinct_on(distinct_on, ctx),
+    }
+}
+
+/// Keep the first row for each `ON` key in `ORDER BY` order
+fn execute_distinct_on<'a, 'p>(
+    distinct_on: &'a DistinctOn,
+    ctx: &'a ExecutionContext<'p>,
+) -> Rows<'a, 'p> {
+    let input = super::execute_plan(&distinct_on.input, ctx);
+    let input: Rows<'a, 'p> = match &distinct_on.sort_expr {
+        Some(sort_expr) => Box::new(sort_rows(input, sort_expr, ctx).into_iter()),
+        None => input,
+    };
+
+    let mut seen = HashSet::new();
+    Box::new(
+        input
+            .filter(move |row| {
+                let key: Vec<ScalarValue> = distinct_on
+                    .on_expr
+                    .iter()
+                    .map(|e| evaluate_expr(e, row, ctx))
+                    .collect();
+                seen.insert(key)
+            })
+            .map(move |row| {
+                project_row(&distinct_on.schema, &distinct_on.select_expr, &row, ctx)
+            }),
+    )
+}
@@ -1,17 +1,15 @@
 use datafusion_common::ScalarValue;
 use datafusion_expr::Filter;
-use pyo3::{types::PyDict, Bound, Python};
 
-use super::{common::evaluate_expr, ExecutionContext};
+use super::{common::evaluate_expr, ExecutionContext, Rows};
 
-pub fn execute<'p>(filter: &Filter, ctx: &ExecutionContext<'p>) -> Vec<Bound<'p, PyDict>> {
+pub fn execute<'a, 'p>(filter: &'a Filter, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
     let input = super::execute_plan(&filter.input, ctx);
 
-    input
-        .into_iter()
-        .filter(|x| match evaluate_expr(&filter.predicate, x) {
-            ScalarValue::Boolean(Some(true)) => true,
-            _ => false,
-        })
-        .collect()
+    Box::new(input.filter(move |x| {
+        matches!(
+            evaluate_expr(&filter.predicate, x, ctx),
+            ScalarValue::Boolean(Some(true))
+        )
+    }))
 }
@@ -1,43 +1,306 @@
-use std::collections::BTreeMap;
+use std::{cell::RefCell, collections::HashMap, iter, rc::Rc};
 
-use datafusion_expr::{Aggregate, Expr, Join, JoinType};
+use datafusion_common::{arrow::datatypes::DataType, Column, ScalarValue};
+use datafusion_expr::{
+    utils::{conjunction, find_valid_equijoin_key_pair, split_conjunction},
+    BinaryExpr, CrossJoin, Expr, ExprSchemable, Join, JoinType, LogicalPlan, Operator,
+};
 use pyo3::{
-    types::{IntoPyDict, PyDict, PyDictMethods},
+    types::{PyDict, PyDictMethods},
     Bound,
 };
 
 use super::{
-    common::{evaluate_agg_expr, evaluate_expr, make_hash, scalar_to_py_any},
-    ExecutionContext,
+    common::{evaluate_expr, get_column, numeric_supertype},
+    ExecutionContext, Rows,
 };
 
-pub fn execute<'p>(join: &Join, ctx: &ExecutionContext<'p>) -> Vec<Bound<'p, PyDict>> {
-    if !matches!(join.join_type, JoinType::Inner) {
-        unimplemented!("Join type {} is not implemented", join.join_type);
+pub fn execute<'a, 'p>(join: &'a Join, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
+    let (on, filter) = split_join_condition(join);
+    execute_join(
+        &join.left,
+        &join.right,
+        join.join_type,
+        on,
+        filter,
+        join.null_equals_null,
+        is_set_operation(join),
+        ctx,
+    )
+}
+
+/// Whether a join is an `INTERSECT` or `EXCEPT`, which DataFusion plans as a
+/// semi (anti) join on every column, by position, where NULLs are equal
+fn is_set_operation(join: &Join) -> bool {
+    let (left, right) = (join.left.schema(), join.right.schema());
+    matches!(join.join_type, JoinType::LeftSemi | JoinType::LeftAnti)
+        && join.null_equals_null
+        && join.filter.is_none()
+        && join.on.len() == left.fields().len()
+        && join.on.len() == right.fields().len()
+        && join
+            .on
+            .iter()
+            .zip(left.fields().iter().zip(right.fields().iter()))
+            .all(|((l, r), (left_field, right_field))| {
+                matches!(l, Expr::Column(c) if c.name == *left_field.name())
+                    && matches!(r, Expr::Column(c) if c.name == *right_field.name())
+            })
+}
+
+pub fn execute_cross_join<'a, 'p>(
+    cross_join: &'a CrossJoin,
+    ctx: &'a ExecutionContext<'p>,
+) -> Rows<'a, 'p> {
+    execute_join(
+        &cross_join.left,
+        &cross_join.right,
+        JoinType::Inner,
+        vec![],
+        None,
+        false,
+        false,
+        ctx,
+    )
+}
+
+/// Join two plans.
+///
+/// Uses a hash join on the `on` key pairs, or a nested loop over all pairs of
+/// rows when there are no equi-join keys. `filter` is applied to every
+/// candidate pair afterwards.
+///
+/// In a `set_operation` each right row is consumed by at most one left row,
+/// which gives the multiset semantics of `INTERSECT ALL` and `EXCEPT ALL`
+/// (the DISTINCT forms dedupe the left side).
+#[allow(clippy::too_many_arguments)]
+fn execute_join<'a, 'p>(
+    left: &'a LogicalPlan,
+    right: &'a LogicalPlan,
+    join_type: JoinType,
+    on: Vec<(Expr, Expr)>,
+    filter: Option<Expr>,
+    null_equals_null: bool,
+    set_operation: bool,
+    ctx: &'a ExecutionContext<'p>,
+) -> Rows<'a, 'p> {
+    let left_columns = left.schema().columns();
+    let right_columns = right.schema().columns();
+    let key_types = key_types(&on, left, right);
+
+    let left = super::execute_plan(left, ctx);
+    let right: Vec<_> = super::execute_plan(right, ctx).collect();
+
+    let (left_keys, right_keys): (Vec<_>, Vec<_>) = on.into_iter().unzip();
+
+    // build side: every right row is kept, rows sharing a key are grouped together.
+    // HashMap confirms equality of the values themselves, so hash collisions can't match.
+    // Without equi-join keys all rows share the empty key, which makes it a nested loop.
+    let mut hash_table: HashMap<Vec<ScalarValue>, Vec<usize>> = HashMap::new();
+    for (i, x) in right.iter().enumerate() {
+        if let Some(key) = evaluate_join_key(&right_keys, &key_types, x, null_equals_null, ctx) {
+            hash_table.entry(key).or_default().push(i);
+        }
     }
-    let join_filter = match &join.filter {
-        Some(Expr::BinaryExpr(binary_expr)) => binary_expr,
-        _ => unimplemented!("join_filter: {:?}", join.filter),
-    };
 
-    let left = super::execute_plan(&join.left, ctx);
-    let right = super::execute_plan(&join.right, ctx);
+    // right rows matched by at least one left row
+    let matched = Rc::new(RefCell::new(vec![false; right.len()]));
+    let right = Rc::new(right);
+
+    // semi and anti joins output rows of one side and need no joined rows
+    // unless there is a filter to check
+    let emits_pairs = matches!(
+        join_type,
+        JoinType::Inner | JoinType::Left | JoinType::Right | JoinType::Full
+    );
+
+    let probe = {
+        let (left_columns, right_columns) = (left_columns.clone(), right_columns.clone());
+        let (matched, right) = (matched.clone(), right.clone());
+        left.flat_map(move |x| {
+            let candidates = evaluate_join_key(&left_keys, &key_types, &x, null_equals_null, ctx)
+                .and_then(|key| hash_table.get(&key))
+                .map_or(&[][..], |indices| indices.as_slice());
 
-    let hash_table: BTreeMap<_, _> = right
-        .into_iter()
-        .map(|x| {
-            let value = evaluate_expr(&join_filter.right, &x);
-            (make_hash(value), x)
+            let mut matched = matched.borrow_mut();
+            let mut found = false;
+            let mut result = vec![];
+            for &i in candidates {
+                if set_operation && matched[i] {
+                    continue;
+                }
+                let row = (emits_pairs || filter.is_some()).then(|| {
+                    join_rows(
+                        Some(&x),
+                        Some(&right[i]),
+                        &left_columns,
+                        &right_columns,
+                        ctx,
+                    )
+                });
+                // residual (non-equi) part of the join condition
+                if let (Some(f), Some(row)) = (&filter, &row) {
+                    if !matches!(evaluate_expr(f, row, ctx), ScalarValue::Boolean(Some(true))) {
+                        continue;
+                    }
+                }
+
+                matched[i] = true;
+                found = true;
+                match row {
+                    Some(row) if emits_pairs => result.push(row),
+                    _ if matches!(join_type, JoinType::LeftSemi | JoinType::LeftAnti) => break,
+                    _ => {}
+                }
+            }
+
+            match join_type {
+                JoinType::Left | JoinType::Full if !found => result.push(join_rows(
+                    Some(&x),
+                    None,
+                    &left_columns,
+                    &right_columns,
+                    ctx,
+                )),
+                JoinType::LeftSemi if found => result.push(x),
+                JoinType::LeftAnti if !found => result.push(x),
+                _ => {}
+            }
+            result
         })
-        .collect();
+    };
 
-    left.into_iter()
-        .filter_map(|x| {
-            let value = evaluate_expr(&join_filter.left, &x);
-            let key = make_hash(value);
-            let result = hash_table.get(&key)?.copy().unwrap();
-            result.update(x.as_mapping()).unwrap();
-            Some(result)
+    // right rows depend on all left rows, so they are emitted once the left side is exhausted
+    let unmatched = iter::once_with(move || {
+        let matched = matched.borrow();
+        let rows = right.iter().zip(matched.iter());
+        match join_type {
+            JoinType::Right | JoinType::Full => rows
+                .filter(|(_, &is_matched)| !is_matched)
+                .map(|(x, _)| join_rows(None, Some(x), &left_columns, &right_columns, ctx))
+                .collect(),
+            JoinType::RightSemi => rows
+                .filter(|(_, &is_matched)| is_matched)
+                .map(|(x, _)| x.clone())
+                .collect(),
+            JoinType::RightAnti => rows
+                .filter(|(_, &is_matched)| !is_matched)
+                .map(|(x, _)| x.clone())
+                .collect(),
+            _ => vec![],
+        }
+    })
+    .flatten();
+
+    Box::new(probe.chain(unmatched))
+}
+
+/// Split the join condition into equi-join key pairs `(left, right)` and a
+/// residual filter.
+///
+/// `JOIN ... USING` is planned into `Join::on`, while `JOIN ... ON` ends up in
+/// `Join::filter`, so equalities between the two sides are extracted from it.
+fn split_join_condition(join: &Join) -> (Vec<(Expr, Expr)>, Option<Expr>) {
+    let mut on = join.on.clone();
+    let mut residual = vec![];
+
+    for expr in join.filter.iter().flat_map(split_conjunction) {
+        let pair = match expr {
+            Expr::BinaryExpr(BinaryExpr {
+                left,
+                op: Operator::Eq,
+                right,
+            }) => find_valid_equijoin_key_pair(
+                left,
+                right,
+                join.left.schema().clone(),
+                join.right.schema().clone(),
+            )
+            .unwrap(),
+            _ => None,
+        };
+        match pair {
+            Some(pair) => on.push(pair),
+            None => residual.push(expr.clone()),
+        }
+    }
+
+    (on, conjunction(residual))
+}
+
+/// Type the keys of each pair are hashed as, `None` unless both are numeric.
+///
+/// Numeric keys of different types are converted to their
+/// `numeric_supertype`, so that `1` matches `1.0`.
+fn key_types(
+    on: &[(Expr, Expr)],
+    left: &LogicalPlan,
+    right: &LogicalPlan,
+) -> Vec<Option<DataType>> {
+    on.iter()
+        .map(|(l, r)| {
+            let left_type = l.get_type(left.schema()).ok()?;
+            let right_type = r.get_type(right.schema()).ok()?;
+            (left_type.is_numeric() && right_type.is_numeric())
+                .then(|| numeric_supertype(&left_type, &right_type))
         })
         .collect()
 }
+
+/// Evaluate the join key of a row, `None` if the row can't match anything
+fn evaluate_join_key<'p>(
+    keys: &[Expr],
+    key_types: &[Option<DataType>],
+    row: &Bound<'p, PyDict>,
+    null_equals_null: bool,
+    ctx: &ExecutionContext<'p>,
+) -> Option<Vec<ScalarValue>> {
+    let key: Vec<_> = keys
+        .iter()
+        .zip(key_types)
+        .map(|(e, key_type)| {
+            let value = evaluate_expr(e, row, ctx);
+            match key_type {
+                // a value of a wider type than its column is left as is rather than truncated
+                Some(key_type)
+                    if value.data_type().is_numeric()
+                        && numeric_supertype(&value.data_type(), key_type) == *key_type =>
+                {
+                    value.cast_to(key_type).unwrap()
+                }
+                _ => value,
+            }
+        })
+        .collect();
+    // NULL never equals anything, unless the join says otherwise
+    if !null_equals_null && key.iter().any(|v| v.is_null()) {
+        return None;
+    }
+    Some(key)
+}
+
+/// Build an output row of a join.
+///
+/// Every column of each side is stored under both its qualified and its
+/// unqualified name. A missing side (the unmatched side of an outer join)
+/// is filled with NULLs.
+fn join_rows<'p>(
+    left: Option<&Bound<'p, PyDict>>,
+    right: Option<&Bound<'p, PyDict>>,
+    left_columns: &[Column],
+    right_columns: &[Column],
+    ctx: &ExecutionContext<'p>,
+) -> Bound<'p, PyDict> {
+    let result = PyDict::new_bound(ctx.py);
+    // left is written last, so it wins when unqualified names clash
+    for (row, columns) in [(right, right_columns), (left, left_columns)] {
+        for column in columns {
+            let value = row.and_then(|row| get_column(column, row));
+            if column.relation.is_some() {
+                result.set_item(column.flat_name(), &value).unwrap();
+            }
+            result.set_item(&column.name, value).unwrap();
+        }
+    }
+    result
+}
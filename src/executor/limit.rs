@@ -0,0 +1,13 @@
+use datafusion_expr::Limit;
+
+use super::{ExecutionContext, Rows};
+
+pub fn execute<'a, 'p>(limit: &'a Limit, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
+    let input = super::execute_plan(&limit.input, ctx).skip(limit.skip);
+
+    // `take` stops pulling rows from the input once `skip + fetch` rows are produced
+    match limit.fetch {
+        Some(fetch) => Box::new(input.take(fetch)),
+        None => Box::new(input),
+    }
+}
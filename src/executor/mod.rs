@@ -1,19 +1,64 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use datafusion_expr::{table_scan, LogicalPlan};
+use datafusion_common::ScalarValue;
+use datafusion_expr::{table_scan, LogicalPlan, SubqueryAlias};
 
 use pyo3::{types::PyDict, Bound, Python};
 
+use crate::errors::SqcError;
+
+mod accumulator;
 mod aggregate;
 mod common;
+mod decorrelate;
+mod distinct;
 mod filter;
 mod join;
+mod limit;
 mod projection;
+mod recursive_query;
+mod sketch;
+mod sort;
+mod subquery;
+mod subquery_alias;
 mod table_scan;
+mod union;
+mod window;
+
+pub use accumulator::{standard, Accumulator};
+pub use common::as_f64;
+pub use decorrelate::decorrelate_predicate_subqueries;
+pub use subquery_alias::shared_subquery_aliases;
+
+/// Lazily produced rows of a plan node.
+pub type Rows<'a, 'p> = Box<dyn Iterator<Item = Bound<'p, PyDict>> + 'a>;
+
+/// Default limit of iterations of a recursive query
+pub const DEFAULT_MAX_RECURSION: usize = 100;
+
+/// A plan, and the values of the outer columns its results depend on
+type PlanKey = (*const LogicalPlan, Vec<ScalarValue>);
+
+/// A shared CTE by number, and the outer values its rows depend on
+type CteKey = (usize, Vec<ScalarValue>);
 
 pub struct ExecutionContext<'p> {
     pub tables: HashMap<String, Vec<Bound<'p, PyDict>>>,
     pub py: Python<'p>,
+    /// Maximum number of iterations of a recursive query
+    pub max_recursion: usize,
+    /// Rows of the previous iteration of recursive queries, by CTE name
+    work_tables: RefCell<HashMap<String, Rc<Vec<Bound<'p, PyDict>>>>>,
+    /// Rows of the enclosing queries while a correlated subquery is executed
+    outer_rows: RefCell<Vec<Bound<'p, PyDict>>>,
+    /// Subquery results by subquery plan and the outer values it depends on
+    subquery_results: RefCell<HashMap<PlanKey, subquery::SubqueryResult>>,
+    /// CTEs referenced more than once in the plan, numbered
+    pub shared_subquery_aliases: HashMap<SubqueryAlias, usize>,
+    /// Rows of CTEs referenced more than once, by CTE number and the outer values it depends on
+    cte_results: RefCell<HashMap<CteKey, Rc<Vec<Bound<'p, PyDict>>>>>,
+    /// First error of the execution, the rows produced are discarded
+    error: RefCell<Option<SqcError>>,
 }
 
 impl<'p> ExecutionContext<'p> {
@@ -21,24 +66,51 @@ impl<'p> ExecutionContext<'p> {
         Self {
             tables: HashMap::new(),
             py,
+            max_recursion: DEFAULT_MAX_RECURSION,
+            work_tables: RefCell::new(HashMap::new()),
+            outer_rows: RefCell::new(Vec::new()),
+            subquery_results: RefCell::new(HashMap::new()),
+            shared_subquery_aliases: HashMap::new(),
+            cte_results: RefCell::new(HashMap::new()),
+            error: RefCell::new(None),
         }
     }
+
+    /// Record an error, the operator that fails stops producing rows
+    pub fn fail(&self, error: SqcError) {
+        self.error.borrow_mut().get_or_insert(error);
+    }
+
+    pub fn take_error(&self) -> Option<SqcError> {
+        self.error.borrow_mut().take()
+    }
 }
 
-pub fn execute_plan<'p>(plan: &LogicalPlan, ctx: &ExecutionContext<'p>) -> Vec<Bound<'p, PyDict>> {
+pub fn execute_plan<'a, 'p>(plan: &'a LogicalPlan, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
     match plan {
         LogicalPlan::TableScan(table_scan) => table_scan::execute(table_scan, ctx),
         LogicalPlan::Projection(projection) => projection::execute(projection, ctx),
         LogicalPlan::Filter(filter) => filter::execute(filter, ctx),
         LogicalPlan::Aggregate(aggregate) => aggregate::execute(aggregate, ctx),
+        LogicalPlan::Window(window) => window::execute(window, ctx),
         LogicalPlan::Join(join) => join::execute(join, ctx),
+        LogicalPlan::CrossJoin(cross_join) => join::execute_cross_join(cross_join, ctx),
+        LogicalPlan::Sort(sort) => sort::execute(sort, ctx),
+        LogicalPlan::Limit(limit) => limit::execute(limit, ctx),
+        LogicalPlan::Distinct(distinct) => distinct::execute(distinct, ctx),
+        LogicalPlan::Union(union) => union::execute(union, ctx),
+        LogicalPlan::SubqueryAlias(subquery_alias) => subquery_alias::execute(subquery_alias, ctx),
+        LogicalPlan::RecursiveQuery(recursive_query) => {
+            recursive_query::execute(recursive_query, ctx)
+        }
+        LogicalPlan::Subquery(subquery) => execute_plan(&subquery.subquery, ctx),
         LogicalPlan::EmptyRelation(empty_relation) => {
             if empty_relation.produce_one_row {
-                vec![PyDict::new_bound(ctx.py)]
+                Box::new(std::iter::once(PyDict::new_bound(ctx.py)))
             } else {
-                vec![]
+                Box::new(std::iter::empty())
             }
-        },
+        }
         _ => unimplemented!("{:?}", plan),
     }
 }
@@ -1,26 +1,55 @@
-use datafusion_expr::{LogicalPlan, Projection};
-use pyo3::types::IntoPyDict;
-use pyo3::{types::PyDict, Bound, Python};
+use datafusion_common::DFSchema;
+use datafusion_expr::{Expr, LogicalPlan, Projection};
+use pyo3::types::{IntoPyDict, PyDict, PyDictMethods};
+use pyo3::Bound;
 
-use super::common::{evaluate_expr, scalar_to_py_any};
-use super::ExecutionContext;
+use super::common::{evaluate_expr, get_column, scalar_to_py_any};
+use super::{ExecutionContext, Rows};
 
-pub fn execute<'p>(projection: &Projection, ctx: &ExecutionContext<'p>) -> Vec<Bound<'p, PyDict>> {
+pub fn execute<'a, 'p>(projection: &'a Projection, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
     let input = super::execute_plan(&projection.input, ctx);
-    input
-        .into_iter()
-        .map(|x| {
-            projection
-                .schema
-                .iter()
-                .map(|(_, field)| field.name())
-                .zip(projection.expr.iter())
-                .map(|(key, expr)| {
-                    let value = scalar_to_py_any(ctx.py, &evaluate_expr(&expr, &x));
-                    (key, value)
-                })
-                .collect::<Vec<_>>()
-                .into_py_dict_bound(ctx.py)
+    Box::new(input.map(move |x| project_row(&projection.schema, &projection.expr, &x, ctx)))
+}
+
+/// Evaluate `exprs` against a row and build a new row named after `schema` fields
+pub fn project_row<'p>(
+    schema: &DFSchema,
+    exprs: &[Expr],
+    row: &Bound<'p, PyDict>,
+    ctx: &ExecutionContext<'p>,
+) -> Bound<'p, PyDict> {
+    schema
+        .iter()
+        .map(|(_, field)| field.name())
+        .zip(exprs.iter())
+        .map(|(key, expr)| {
+            let value = scalar_to_py_any(ctx.py, &evaluate_expr(expr, row, ctx));
+            (key, value)
         })
-        .collect()
+        .collect::<Vec<_>>()
+        .into_py_dict_bound(ctx.py)
+}
+
+/// Rename columns of `input` rows after the fields of `schema` by position.
+///
+/// Rows are passed through as is when the names already match.
+pub fn rename_columns<'a, 'p>(
+    rows: Rows<'a, 'p>,
+    input: &DFSchema,
+    schema: &DFSchema,
+    ctx: &'a ExecutionContext<'p>,
+) -> Rows<'a, 'p> {
+    let columns = input.columns();
+    let names: Vec<_> = schema.fields().iter().map(|f| f.name().clone()).collect();
+    if columns.iter().map(|c| &c.name).eq(names.iter()) {
+        return rows;
+    }
+
+    Box::new(rows.map(move |row| {
+        let result = PyDict::new_bound(ctx.py);
+        for (name, column) in names.iter().zip(columns.iter()) {
+            result.set_item(name, get_column(column, &row)).unwrap();
+        }
+        result
+    }))
 }
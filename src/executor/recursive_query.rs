@@ -0,0 +1,61 @@
+use std::{iter, mem, rc::Rc};
+
+use datafusion_expr::RecursiveQuery;
+
+use crate::errors::SqcError;
+
+use super::{projection::rename_columns, ExecutionContext, Rows};
+
+/// `WITH RECURSIVE`: the static term is evaluated first, then the recursive term
+/// is evaluated over the rows produced by the previous iteration (the work table)
+/// until it produces no new rows.
+///
+/// Rows are produced lazily, so a `LIMIT` above stops the recursion early.
+/// Reaching `max_recursion` iterations ends the rows and fails the query.
+pub fn execute<'a, 'p>(
+    recursive_query: &'a RecursiveQuery,
+    ctx: &'a ExecutionContext<'p>,
+) -> Rows<'a, 'p> {
+    let static_term = &recursive_query.static_term;
+    let recursive_term = &recursive_query.recursive_term;
+    let name = &recursive_query.name;
+
+    let mut batch = super::execute_plan(static_term, ctx);
+    let mut work_table = vec![];
+    let mut iteration = 0;
+
+    Box::new(iter::from_fn(move || loop {
+        if let Some(row) = batch.next() {
+            work_table.push(row.clone());
+            return Some(row);
+        }
+
+        // fixpoint: the last iteration produced no rows
+        if work_table.is_empty() {
+            ctx.work_tables.borrow_mut().remove(name);
+            return None;
+        }
+
+        iteration += 1;
+        if iteration > ctx.max_recursion {
+            ctx.work_tables.borrow_mut().remove(name);
+            ctx.fail(SqcError::MaxRecursion {
+                name: name.clone(),
+                max_recursion: ctx.max_recursion,
+            });
+            return None;
+        }
+
+        ctx.work_tables
+            .borrow_mut()
+            .insert(name.clone(), Rc::new(mem::take(&mut work_table)));
+        // subqueries and CTEs of the recursive term may read the work table,
+        // so their results of the previous iteration are stale
+        ctx.subquery_results.borrow_mut().clear();
+        ctx.cte_results.borrow_mut().clear();
+
+        // rows of the recursive term are named after the static term
+        let rows = super::execute_plan(recursive_term, ctx);
+        batch = rename_columns(rows, recursive_term.schema(), static_term.schema(), ctx);
+    }))
+}
@@ -0,0 +1,168 @@
+//! Fixed-size summaries of large inputs for approximate aggregates.
+//!
+//! Both sketches can be merged, so partial summaries (of chunks of the input
+//! or of finer groups) combine into the summary of the whole.
+
+use std::f64::consts::PI;
+
+/// Bits of the hash choosing the register, 4096 registers give a standard
+/// error of about 1.6%
+const HLL_PRECISION: u32 = 12;
+
+/// HyperLogLog estimate of the number of distinct hashes
+#[derive(Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+}
+
+impl HyperLogLog {
+    pub fn add(&mut self, hash: u64) {
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        // position of the first set bit of the rest of the hash
+        let rest = hash << HLL_PRECISION | 1 << (HLL_PRECISION - 1);
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        // linear counting is more accurate while many registers are empty
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// Default compression of a t-digest, roughly its maximum number of centroids
+pub const DEFAULT_COMPRESSION: f64 = 100.0;
+
+/// Merging t-digest, quantiles of a distribution with most precision at the
+/// tails
+#[derive(Clone)]
+pub struct TDigest {
+    compression: f64,
+    /// Sorted `(mean, weight)` of the merged centroids
+    centroids: Vec<(f64, f64)>,
+    /// Values added since the last compression
+    buffer: Vec<f64>,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> Self {
+        Self {
+            compression,
+            centroids: vec![],
+            buffer: vec![],
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buffer.push(value);
+        if self.buffer.len() as f64 > 5.0 * self.compression {
+            self.compress(vec![]);
+        }
+    }
+
+    pub fn merge(&mut self, other: &TDigest) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        let mut centroids = other.centroids.clone();
+        centroids.extend(other.buffer.iter().map(|&v| (v, 1.0)));
+        self.compress(centroids);
+    }
+
+    /// Merge the buffer and `centroids` into the centroids, each one covering
+    /// at most one unit of the scale function `k`
+    fn compress(&mut self, centroids: Vec<(f64, f64)>) {
+        let mut items = std::mem::take(&mut self.centroids);
+        items.extend(centroids);
+        items.extend(self.buffer.drain(..).map(|v| (v, 1.0)));
+        if items.is_empty() {
+            return;
+        }
+        items.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let total: f64 = items.iter().map(|(_, weight)| weight).sum();
+        let delta = self.compression;
+        let k = |q: f64| delta / (2.0 * PI) * (2.0 * q - 1.0).asin();
+        let k_inverse = |k: f64| ((k * 2.0 * PI / delta).sin() + 1.0) / 2.0;
+
+        let mut merged = Vec::with_capacity(delta as usize);
+        let mut current = items[0];
+        let mut weight_before = 0.0;
+        let mut q_limit = k_inverse(k(0.0) + 1.0);
+        for &(mean, weight) in &items[1..] {
+            if (weight_before + current.1 + weight) / total <= q_limit {
+                let sum = current.1 + weight;
+                current = (current.0 + (mean - current.0) * weight / sum, sum);
+            } else {
+                weight_before += current.1;
+                merged.push(current);
+                q_limit = k_inverse(k(weight_before / total) + 1.0);
+                current = (mean, weight);
+            }
+        }
+        merged.push(current);
+        self.centroids = merged;
+    }
+
+    /// Interpolated value at quantile `q`, `None` for an empty digest
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let mut digest = self.clone();
+        digest.compress(vec![]);
+        let centroids = &digest.centroids;
+        if centroids.is_empty() {
+            return None;
+        }
+
+        // the mean of a centroid sits at the middle of its weight
+        let total: f64 = centroids.iter().map(|(_, weight)| weight).sum();
+        let target = q.clamp(0.0, 1.0) * total;
+        let mut previous = (self.min, 0.0);
+        let mut weight_before = 0.0;
+        for &(mean, weight) in centroids {
+            let center = weight_before + weight / 2.0;
+            if target < center {
+                return Some(interpolate(previous, (mean, center), target));
+            }
+            previous = (mean, center);
+            weight_before += weight;
+        }
+        Some(interpolate(previous, (self.max, total), target))
+    }
+}
+
+/// Value at `position` on the line between two `(value, position)` points
+fn interpolate(low: (f64, f64), high: (f64, f64), position: f64) -> f64 {
+    if high.1 <= low.1 {
+        return high.0;
+    }
+    low.0 + (high.0 - low.0) * (position - low.1) / (high.1 - low.1)
+}
@@ -0,0 +1,78 @@
+use std::cmp::Ordering;
+
+use datafusion_common::ScalarValue;
+use datafusion_expr::{expr, Expr, Sort};
+use pyo3::{types::PyDict, Bound};
+
+use super::{
+    common::{compare_values, evaluate_expr},
+    ExecutionContext, Rows,
+};
+
+pub fn execute<'a, 'p>(sort: &'a Sort, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
+    let input = super::execute_plan(&sort.input, ctx);
+    let rows = sort_rows(input, &sort.expr, ctx).into_iter();
+
+    match sort.fetch {
+        Some(fetch) => Box::new(rows.take(fetch)),
+        None => Box::new(rows),
+    }
+}
+
+/// Materialize and sort rows by `ORDER BY` expressions (`Expr::Sort`)
+pub fn sort_rows<'p>(
+    input: impl Iterator<Item = Bound<'p, PyDict>>,
+    exprs: &[Expr],
+    ctx: &ExecutionContext<'p>,
+) -> Vec<Bound<'p, PyDict>> {
+    let sort_exprs = as_sort_exprs(exprs);
+
+    // evaluate sort keys once per row
+    let mut keyed: Vec<(Vec<ScalarValue>, Bound<'p, PyDict>)> = input
+        .map(|row| {
+            let key = sort_exprs
+                .iter()
+                .map(|e| evaluate_expr(&e.expr, &row, ctx))
+                .collect();
+            (key, row)
+        })
+        .collect();
+
+    // stable sort keeps the input order of rows with equal keys
+    keyed.sort_by(|(left, _), (right, _)| compare_keys(&sort_exprs, left, right));
+
+    keyed.into_iter().map(|(_, row)| row).collect()
+}
+
+pub fn as_sort_exprs(exprs: &[Expr]) -> Vec<&expr::Sort> {
+    exprs
+        .iter()
+        .map(|e| match e {
+            Expr::Sort(sort_expr) => sort_expr,
+            _ => panic!("Unsupported sort expression: {:?}", e),
+        })
+        .collect()
+}
+
+/// Compare two sort keys according to the direction and NULLs placement
+/// of each `ORDER BY` expression.
+pub fn compare_keys(
+    sort_exprs: &[&expr::Sort],
+    left: &[ScalarValue],
+    right: &[ScalarValue],
+) -> Ordering {
+    sort_exprs
+        .iter()
+        .zip(left.iter().zip(right.iter()))
+        .map(|(e, (l, r))| match (l.is_null(), r.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) if e.nulls_first => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (false, true) if e.nulls_first => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) if e.asc => compare_values(l, r),
+            (false, false) => compare_values(l, r).reverse(),
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
@@ -0,0 +1,134 @@
+use std::{collections::HashSet, rc::Rc, sync::Arc};
+
+use datafusion_common::ScalarValue;
+use datafusion_expr::{
+    expr::{Exists, InSubquery},
+    Subquery,
+};
+use pyo3::{types::PyDict, Bound};
+
+use super::{
+    common::{evaluate_columns, evaluate_expr},
+    ExecutionContext, Rows,
+};
+
+#[derive(Clone)]
+pub enum SubqueryResult {
+    Exists(bool),
+    /// Distinct values of the only column, NULLs excluded, and whether there was a NULL
+    Values(Rc<HashSet<ScalarValue>>, bool),
+    Scalar(ScalarValue),
+}
+
+/// Evaluate a subquery for the current row.
+///
+/// The result is memoized by the values of the outer columns the subquery refers
+/// to, so an uncorrelated subquery runs once and a correlated one runs once per
+/// distinct combination of outer values.
+fn evaluate_subquery<'p>(
+    subquery: &Subquery,
+    row: &Bound<'p, PyDict>,
+    ctx: &ExecutionContext<'p>,
+    evaluate: impl FnOnce(Rows<'_, 'p>) -> SubqueryResult,
+) -> SubqueryResult {
+    ctx.outer_rows.borrow_mut().push(row.clone());
+
+    let outer_values: Vec<_> = subquery
+        .outer_ref_columns
+        .iter()
+        .map(|e| evaluate_expr(e, row, ctx))
+        .collect();
+    let key = (Arc::as_ptr(&subquery.subquery), outer_values);
+
+    let cached = ctx.subquery_results.borrow().get(&key).cloned();
+    let result = match cached {
+        Some(result) => result,
+        None => {
+            let result = evaluate(super::execute_plan(&subquery.subquery, ctx));
+            ctx.subquery_results
+                .borrow_mut()
+                .insert(key, result.clone());
+            result
+        }
+    };
+
+    ctx.outer_rows.borrow_mut().pop();
+    result
+}
+
+pub fn evaluate_exists<'p>(
+    exists: &Exists,
+    row: &Bound<'p, PyDict>,
+    ctx: &ExecutionContext<'p>,
+) -> ScalarValue {
+    // a single row is enough to answer
+    let result = evaluate_subquery(&exists.subquery, row, ctx, |mut rows| {
+        SubqueryResult::Exists(rows.next().is_some())
+    });
+
+    match result {
+        SubqueryResult::Exists(found) => ScalarValue::Boolean(Some(found != exists.negated)),
+        _ => unreachable!(),
+    }
+}
+
+/// `expr [NOT] IN (subquery)` with SQL semantics: NULL if `expr` is NULL, or if
+/// there is no match and the subquery returned a NULL.
+pub fn evaluate_in_subquery<'p>(
+    in_subquery: &InSubquery,
+    row: &Bound<'p, PyDict>,
+    ctx: &ExecutionContext<'p>,
+) -> ScalarValue {
+    let value = evaluate_expr(&in_subquery.expr, row, ctx);
+    if value.is_null() {
+        return ScalarValue::Boolean(None);
+    }
+
+    let columns = in_subquery.subquery.subquery.schema().columns();
+    let result = evaluate_subquery(&in_subquery.subquery, row, ctx, |rows| {
+        let mut values = HashSet::new();
+        let mut has_null = false;
+        for row in rows {
+            match evaluate_columns(&columns, &row).swap_remove(0) {
+                v if v.is_null() => has_null = true,
+                v => {
+                    values.insert(v);
+                }
+            }
+        }
+        SubqueryResult::Values(Rc::new(values), has_null)
+    });
+
+    match result {
+        SubqueryResult::Values(values, _) if values.contains(&value) => {
+            ScalarValue::Boolean(Some(!in_subquery.negated))
+        }
+        SubqueryResult::Values(_, true) => ScalarValue::Boolean(None),
+        SubqueryResult::Values(_, false) => ScalarValue::Boolean(Some(in_subquery.negated)),
+        _ => unreachable!(),
+    }
+}
+
+/// `(subquery)` used as a value: NULL if it returns no rows
+pub fn evaluate_scalar_subquery<'p>(
+    subquery: &Subquery,
+    row: &Bound<'p, PyDict>,
+    ctx: &ExecutionContext<'p>,
+) -> ScalarValue {
+    let columns = subquery.subquery.schema().columns();
+    let result = evaluate_subquery(subquery, row, ctx, |mut rows| {
+        let value = match rows.next() {
+            Some(row) => evaluate_columns(&columns, &row).swap_remove(0),
+            None => ScalarValue::Null,
+        };
+        if rows.next().is_some() {
+            panic!("Scalar subquery returned more than one row");
+        }
+        SubqueryResult::Scalar(value)
+    });
+
+    match result {
+        SubqueryResult::Scalar(value) => value,
+        _ => unreachable!(),
+    }
+}
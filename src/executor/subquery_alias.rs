@@ -0,0 +1,64 @@
+use std::{collections::HashMap, rc::Rc};
+
+use datafusion_common::tree_node::TreeNodeRecursion;
+use datafusion_expr::{LogicalPlan, SubqueryAlias};
+use pyo3::types::PyDict;
+
+use super::{common::evaluate_expr, projection::rename_columns, ExecutionContext, Rows};
+
+pub fn execute<'a, 'p>(
+    subquery_alias: &'a SubqueryAlias,
+    ctx: &'a ExecutionContext<'p>,
+) -> Rows<'a, 'p> {
+    let input = &subquery_alias.input;
+
+    // A CTE referenced more than once is evaluated once and the rows are
+    // reused, once per outer row it depends on inside a correlated subquery.
+    let shared = ctx.shared_subquery_aliases.get(subquery_alias);
+    let rows: Rows<'a, 'p> = if let Some(&number) = shared {
+        let no_row = PyDict::new_bound(ctx.py);
+        let outer_values: Vec<_> = input
+            .all_out_ref_exprs()
+            .iter()
+            .map(|e| evaluate_expr(e, &no_row, ctx))
+            .collect();
+        let key = (number, outer_values);
+        let cached = ctx.cte_results.borrow().get(&key).cloned();
+        let rows = match cached {
+            Some(rows) => rows,
+            None => {
+                let rows = Rc::new(super::execute_plan(input, ctx).collect::<Vec<_>>());
+                ctx.cte_results.borrow_mut().insert(key, rows.clone());
+                rows
+            }
+        };
+        Box::new((0..rows.len()).map(move |i| rows[i].clone()))
+    } else {
+        super::execute_plan(input, ctx)
+    };
+
+    // duplicate column names of the input get renamed by the alias
+    rename_columns(rows, input.schema(), &subquery_alias.schema, ctx)
+}
+
+/// Number the subquery aliases that occur more than once in a plan, including
+/// its subqueries.
+///
+/// Every reference to a CTE is planned as the same subquery alias, so these
+/// are the CTEs referenced more than once.
+pub fn shared_subquery_aliases(plan: &LogicalPlan) -> HashMap<SubqueryAlias, usize> {
+    let mut references: HashMap<SubqueryAlias, usize> = HashMap::new();
+    plan.apply_with_subqueries(&mut |plan: &LogicalPlan| {
+        if let LogicalPlan::SubqueryAlias(subquery_alias) = plan {
+            *references.entry(subquery_alias.clone()).or_default() += 1;
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })
+    .unwrap();
+    references
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .enumerate()
+        .map(|(number, (subquery_alias, _))| (subquery_alias, number))
+        .collect()
+}
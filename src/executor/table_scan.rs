@@ -1,22 +1,27 @@
 use datafusion_common::ScalarValue;
 use datafusion_expr::TableScan;
-use pyo3::{types::PyDict, Bound};
 
-use super::{common::evaluate_expr, ExecutionContext};
+use super::{common::evaluate_expr, ExecutionContext, Rows};
 
-pub fn execute<'p>(table_scan: &TableScan, ctx: &ExecutionContext<'p>) -> Vec<Bound<'p, PyDict>> {
-    let values = ctx.tables.get(table_scan.table_name.table()).unwrap();
-    values
-        .into_iter()
-        .filter(|x| {
-            table_scan
-                .filters
-                .iter()
-                .all(|f| match evaluate_expr(f, x) {
-                    ScalarValue::Boolean(Some(true)) => true,
-                    _ => false,
-                })
-        })
-        .cloned()
-        .collect()
+pub fn execute<'a, 'p>(table_scan: &'a TableScan, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
+    let name = table_scan.table_name.table();
+    // the work table of a recursive query shadows tables with the same name
+    let values: Rows<'a, 'p> = match ctx.work_tables.borrow().get(name) {
+        Some(values) => {
+            let values = values.clone();
+            Box::new((0..values.len()).map(move |i| values[i].clone()))
+        }
+        None => Box::new(ctx.tables.get(name).unwrap().iter().cloned()),
+    };
+
+    Box::new(
+        values
+            .filter(move |x| {
+                table_scan
+                    .filters
+                    .iter()
+                    .all(|f| matches!(evaluate_expr(f, x, ctx), ScalarValue::Boolean(Some(true))))
+            })
+            .take(table_scan.fetch.unwrap_or(usize::MAX)),
+    )
 }
@@ -0,0 +1,11 @@
+use datafusion_expr::Union;
+
+use super::{projection::rename_columns, ExecutionContext, Rows};
+
+pub fn execute<'a, 'p>(union: &'a Union, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
+    // output columns are named after the first input
+    Box::new(union.inputs.iter().flat_map(move |input| {
+        let rows = super::execute_plan(input, ctx);
+        rename_columns(rows, input.schema(), &union.schema, ctx)
+    }))
+}
@@ -0,0 +1,602 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    ops::Range,
+    slice,
+};
+
+use datafusion_common::ScalarValue;
+use datafusion_expr::{
+    expr::{self, WindowFunction},
+    AggregateFunction, BuiltInWindowFunction, Expr, Window, WindowFrame, WindowFrameBound,
+    WindowFrameUnits, WindowFunctionDefinition,
+};
+use pyo3::{
+    types::{PyDict, PyDictMethods},
+    Bound,
+};
+
+use crate::errors::{SqcError, SqcResult};
+
+use super::{
+    common::{
+        as_f64, compare_values, evaluate_expr, promote_numeric, scalar_to_py_any, MILLIS_PER_DAY,
+    },
+    sort::{as_sort_exprs, compare_keys},
+    ExecutionContext, Rows,
+};
+
+pub fn execute<'a, 'p>(window: &'a Window, ctx: &'a ExecutionContext<'p>) -> Rows<'a, 'p> {
+    let rows: Vec<_> = super::execute_plan(&window.input, ctx).collect();
+
+    // window columns follow the input columns in the schema
+    let input_len = window.input.schema().fields().len();
+    let names: Vec<_> = window.schema.fields()[input_len..]
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+
+    let columns: Vec<_> = window
+        .window_expr
+        .iter()
+        .map(|e| evaluate_window_expr(e, &rows, ctx))
+        .collect();
+
+    Box::new(rows.into_iter().enumerate().map(move |(i, row)| {
+        let result = row.copy().unwrap();
+        for (name, values) in names.iter().zip(columns.iter()) {
+            result
+                .set_item(name, scalar_to_py_any(ctx.py, &values[i]))
+                .unwrap();
+        }
+        result
+    }))
+}
+
+/// Rows of a partition in `ORDER BY` order
+struct Partition {
+    /// Indices of the rows in the input
+    indices: Vec<usize>,
+    /// `ORDER BY` keys of each row
+    keys: Vec<Vec<ScalarValue>>,
+    /// Range of the peers (rows with equal `ORDER BY` keys) of each row
+    peers: Vec<Range<usize>>,
+}
+
+/// Evaluate a window function for every input row
+fn evaluate_window_expr<'p>(
+    expr: &Expr,
+    rows: &[Bound<'p, PyDict>],
+    ctx: &ExecutionContext<'p>,
+) -> Vec<ScalarValue> {
+    let window_function = match expr {
+        Expr::WindowFunction(window_function) => window_function,
+        Expr::Alias(alias) => return evaluate_window_expr(&alias.expr, rows, ctx),
+        _ => panic!("Unsupported window expression: {:?}", expr),
+    };
+    if window_function.null_treatment.is_some() {
+        unimplemented!("IGNORE NULLS / RESPECT NULLS in window functions");
+    }
+
+    let mut result = vec![ScalarValue::Null; rows.len()];
+    for partition in partition_rows(window_function, rows, ctx) {
+        let values = evaluate_partition(window_function, &partition, rows, ctx);
+        for (i, value) in partition.indices.into_iter().zip(values) {
+            result[i] = value;
+        }
+    }
+    result
+}
+
+/// Split rows by `PARTITION BY` keys (in first-seen order) and sort each
+/// partition by `ORDER BY` keys
+fn partition_rows<'p>(
+    window_function: &WindowFunction,
+    rows: &[Bound<'p, PyDict>],
+    ctx: &ExecutionContext<'p>,
+) -> Vec<Partition> {
+    let mut positions: HashMap<Vec<ScalarValue>, usize> = HashMap::new();
+    let mut partitions: Vec<Vec<usize>> = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        let key: Vec<_> = window_function
+            .partition_by
+            .iter()
+            .map(|e| evaluate_expr(e, row, ctx))
+            .collect();
+        let position = *positions.entry(key).or_insert_with(|| {
+            partitions.push(vec![]);
+            partitions.len() - 1
+        });
+        partitions[position].push(i);
+    }
+
+    let sort_exprs = as_sort_exprs(&window_function.order_by);
+    partitions
+        .into_iter()
+        .map(|indices| {
+            let mut keyed: Vec<(Vec<ScalarValue>, usize)> = indices
+                .into_iter()
+                .map(|i| {
+                    let key = sort_exprs
+                        .iter()
+                        .map(|e| evaluate_expr(&e.expr, &rows[i], ctx))
+                        .collect();
+                    (key, i)
+                })
+                .collect();
+            keyed.sort_by(|(left, _), (right, _)| compare_keys(&sort_exprs, left, right));
+
+            // without ORDER BY all rows of the partition are peers
+            let mut peers = vec![0..0; keyed.len()];
+            let mut start = 0;
+            for end in 1..=keyed.len() {
+                let is_last_peer = end == keyed.len()
+                    || compare_keys(&sort_exprs, &keyed[start].0, &keyed[end].0).is_ne();
+                if is_last_peer {
+                    peers[start..end].fill(start..end);
+                    start = end;
+                }
+            }
+
+            let (keys, indices) = keyed.into_iter().unzip();
+            Partition {
+                indices,
+                keys,
+                peers,
+            }
+        })
+        .collect()
+}
+
+/// Evaluate a window function over a sorted partition
+fn evaluate_partition<'p>(
+    window_function: &WindowFunction,
+    partition: &Partition,
+    rows: &[Bound<'p, PyDict>],
+    ctx: &ExecutionContext<'p>,
+) -> Vec<ScalarValue> {
+    let n = partition.indices.len();
+    // argument of the function for each row, in partition order
+    let evaluate_arg = |i: usize| -> Vec<ScalarValue> {
+        partition
+            .indices
+            .iter()
+            .map(|&row| match &window_function.args[i] {
+                // COUNT(*) counts rows, any non-NULL value does
+                Expr::Wildcard { .. } => ScalarValue::Boolean(Some(true)),
+                arg => evaluate_expr(arg, &rows[row], ctx),
+            })
+            .collect()
+    };
+    let frames = || evaluate_frames(window_function, partition);
+
+    match &window_function.fun {
+        WindowFunctionDefinition::BuiltInWindowFunction(fun) => match fun {
+            BuiltInWindowFunction::RowNumber => (1..=n as i64).map(ScalarValue::from).collect(),
+            BuiltInWindowFunction::Rank => partition
+                .peers
+                .iter()
+                .map(|peers| ScalarValue::from(peers.start as i64 + 1))
+                .collect(),
+            BuiltInWindowFunction::DenseRank => {
+                let mut rank = 0i64;
+                (0..n)
+                    .map(|i| {
+                        if partition.peers[i].start == i {
+                            rank += 1;
+                        }
+                        ScalarValue::from(rank)
+                    })
+                    .collect()
+            }
+            BuiltInWindowFunction::PercentRank => partition
+                .peers
+                .iter()
+                .map(|peers| match n {
+                    1 => ScalarValue::from(0.0),
+                    _ => ScalarValue::from(peers.start as f64 / (n - 1) as f64),
+                })
+                .collect(),
+            BuiltInWindowFunction::CumeDist => partition
+                .peers
+                .iter()
+                .map(|peers| ScalarValue::from(peers.end as f64 / n as f64))
+                .collect(),
+            BuiltInWindowFunction::Ntile => {
+                let buckets = match evaluate_arg(0).swap_remove(0) {
+                    ScalarValue::Int64(Some(buckets)) if buckets > 0 => buckets as usize,
+                    value => panic!("NTILE argument must be a positive integer, got {}", value),
+                };
+                // the first `n % buckets` buckets get one more row
+                let (size, rest) = (n / buckets, n % buckets);
+                (0..n)
+                    .map(|i| {
+                        let bucket = match i < rest * (size + 1) {
+                            true => i / (size + 1),
+                            false => rest + (i - rest * (size + 1)) / size,
+                        };
+                        ScalarValue::from(bucket as i64 + 1)
+                    })
+                    .collect()
+            }
+            BuiltInWindowFunction::Lag | BuiltInWindowFunction::Lead => {
+                let values = evaluate_arg(0);
+                let offset = match window_function.args.len() {
+                    1 => 1,
+                    _ => match evaluate_arg(1).swap_remove(0) {
+                        ScalarValue::Int64(Some(offset)) => offset,
+                        value => panic!("{} offset must be an integer, got {}", fun, value),
+                    },
+                };
+                let offset = match fun {
+                    BuiltInWindowFunction::Lag => -offset,
+                    _ => offset,
+                };
+                let defaults = match window_function.args.len() {
+                    3 => evaluate_arg(2),
+                    _ => vec![ScalarValue::Null; n],
+                };
+                (0..n)
+                    .map(|i| match usize::try_from(i as i64 + offset) {
+                        Ok(j) if j < n => values[j].clone(),
+                        _ => defaults[i].clone(),
+                    })
+                    .collect()
+            }
+            BuiltInWindowFunction::FirstValue
+            | BuiltInWindowFunction::LastValue
+            | BuiltInWindowFunction::NthValue => {
+                let values = evaluate_arg(0);
+                let nth = match fun {
+                    BuiltInWindowFunction::NthValue => match evaluate_arg(1).swap_remove(0) {
+                        ScalarValue::Int64(Some(nth)) if nth > 0 => nth as usize,
+                        value => panic!(
+                            "NTH_VALUE argument must be a positive integer, got {}",
+                            value
+                        ),
+                    },
+                    _ => 1,
+                };
+                frames()
+                    .into_iter()
+                    .map(|frame| {
+                        let position = match fun {
+                            BuiltInWindowFunction::LastValue => frame.end.checked_sub(1),
+                            _ => Some(frame.start + nth - 1),
+                        };
+                        match position {
+                            Some(j) if frame.contains(&j) => values[j].clone(),
+                            _ => ScalarValue::Null,
+                        }
+                    })
+                    .collect()
+            }
+        },
+        WindowFunctionDefinition::AggregateFunction(fun) => {
+            let values = evaluate_arg(0);
+            let mut aggregate = SlidingAggregate::new(fun);
+            let (mut added, mut retracted) = (0, 0);
+            // frames only move forward, so every row is added and retracted at most once
+            frames()
+                .into_iter()
+                .map(|frame| {
+                    for j in added..frame.end {
+                        aggregate.add(j, &values)?;
+                    }
+                    added = added.max(frame.end);
+                    for j in retracted..frame.start {
+                        aggregate.retract(j, &values)?;
+                    }
+                    retracted = retracted.max(frame.start);
+                    Ok(aggregate.evaluate(&values))
+                })
+                .collect::<SqcResult<_>>()
+                .unwrap_or_else(|error| {
+                    ctx.fail(error);
+                    vec![ScalarValue::Null; n]
+                })
+        }
+        _ => unimplemented!("Window function {} is not implemented", window_function.fun),
+    }
+}
+
+/// Frame (range of rows of the partition) of each row.
+///
+/// Offsets are constant, so both ends of the frame only move forward along the
+/// partition and `RANGE` bounds are found with one pass of a pointer per end.
+fn evaluate_frames(window_function: &WindowFunction, partition: &Partition) -> Vec<Range<usize>> {
+    let WindowFrame {
+        units,
+        start_bound,
+        end_bound,
+        ..
+    } = &window_function.window_frame;
+    let n = partition.indices.len();
+
+    match units {
+        WindowFrameUnits::Rows => (0..n)
+            .map(|i| {
+                let start = match start_bound {
+                    WindowFrameBound::Preceding(offset) if offset.is_null() => 0,
+                    WindowFrameBound::Preceding(offset) => i.saturating_sub(rows_offset(offset)),
+                    WindowFrameBound::CurrentRow => i,
+                    WindowFrameBound::Following(offset) => n.min(i + rows_offset(offset)),
+                };
+                let end = match end_bound {
+                    WindowFrameBound::Following(offset) if offset.is_null() => n,
+                    WindowFrameBound::Following(offset) => n.min(i + rows_offset(offset) + 1),
+                    WindowFrameBound::CurrentRow => i + 1,
+                    WindowFrameBound::Preceding(offset) => {
+                        (i + 1).saturating_sub(rows_offset(offset))
+                    }
+                };
+                start..end.max(start)
+            })
+            .collect(),
+        WindowFrameUnits::Range => {
+            let sort_exprs = as_sort_exprs(&window_function.order_by);
+            let (mut start, mut end) = (0, 0);
+            (0..n)
+                .map(|i| {
+                    // a NULL key has no distance to other keys, only its peers are in range
+                    let key = partition.keys[i].first().filter(|key| !key.is_null());
+                    let start = match start_bound {
+                        WindowFrameBound::Preceding(offset) if offset.is_null() => 0,
+                        _ if key.is_none() => partition.peers[i].start,
+                        WindowFrameBound::CurrentRow => partition.peers[i].start,
+                        bound => {
+                            let target = range_bound(sort_exprs[0], key.unwrap(), bound);
+                            while start < n
+                                && compare_range_keys(
+                                    sort_exprs[0],
+                                    &partition.keys[start],
+                                    &target,
+                                )
+                                .is_lt()
+                            {
+                                start += 1;
+                            }
+                            start
+                        }
+                    };
+                    let end = match end_bound {
+                        WindowFrameBound::Following(offset) if offset.is_null() => n,
+                        _ if key.is_none() => partition.peers[i].end,
+                        WindowFrameBound::CurrentRow => partition.peers[i].end,
+                        bound => {
+                            let target = range_bound(sort_exprs[0], key.unwrap(), bound);
+                            while end < n
+                                && compare_range_keys(sort_exprs[0], &partition.keys[end], &target)
+                                    .is_le()
+                            {
+                                end += 1;
+                            }
+                            end
+                        }
+                    };
+                    start..end.max(start)
+                })
+                .collect()
+        }
+        WindowFrameUnits::Groups => unimplemented!("GROUPS window frames"),
+    }
+}
+
+/// Offset of a `ROWS` frame bound, which the planner leaves as a string
+fn rows_offset(offset: &ScalarValue) -> usize {
+    match offset {
+        ScalarValue::Utf8(Some(offset)) => offset
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid ROWS frame offset: {}", offset)),
+        ScalarValue::UInt64(Some(offset)) => *offset as usize,
+        _ => panic!("Invalid ROWS frame offset: {}", offset),
+    }
+}
+
+/// `ORDER BY` key at the given `RANGE` bound of a row with the given key
+fn range_bound(sort_expr: &expr::Sort, key: &ScalarValue, bound: &WindowFrameBound) -> ScalarValue {
+    let (offset, following) = match bound {
+        WindowFrameBound::Preceding(offset) => (offset, false),
+        WindowFrameBound::Following(offset) => (offset, true),
+        WindowFrameBound::CurrentRow => return key.clone(),
+    };
+    let offset = match offset {
+        ScalarValue::Utf8(Some(offset)) => offset.as_str(),
+        _ => panic!("Invalid RANGE frame offset: {}", offset),
+    };
+
+    // "preceding" rows come before in sort order, which is a greater key when descending
+    let forward = following == sort_expr.asc;
+    let result = match key {
+        ScalarValue::Int64(Some(key)) => offset
+            .parse::<i64>()
+            .ok()
+            .map(|offset| ScalarValue::from(if forward { key + offset } else { key - offset })),
+        ScalarValue::Float64(Some(key)) => offset
+            .parse::<f64>()
+            .ok()
+            .map(|offset| ScalarValue::from(if forward { key + offset } else { key - offset })),
+        ScalarValue::Date64(Some(key)) => interval_millis(offset).map(|offset| {
+            ScalarValue::Date64(Some(if forward { key + offset } else { key - offset }))
+        }),
+        _ => None,
+    };
+    result.unwrap_or_else(|| panic!("Invalid RANGE frame offset {} for {}", offset, key))
+}
+
+/// Compare the `ORDER BY` key of a row with a `RANGE` bound
+fn compare_range_keys(
+    sort_expr: &expr::Sort,
+    key: &[ScalarValue],
+    bound: &ScalarValue,
+) -> Ordering {
+    compare_keys(slice::from_ref(&sort_expr), key, slice::from_ref(bound))
+}
+
+/// Length of an interval like `1 DAY` or `2 weeks` in milliseconds.
+///
+/// Months and years have no fixed length and aren't supported.
+fn interval_millis(interval: &str) -> Option<i64> {
+    let mut parts = interval.split_whitespace();
+    let value: i64 = parts.next()?.parse().ok()?;
+    let unit = parts.next()?.to_lowercase();
+    let millis = match unit.trim_end_matches('s') {
+        "week" => 7 * MILLIS_PER_DAY,
+        "day" => MILLIS_PER_DAY,
+        "hour" => 3_600_000,
+        "minute" => 60_000,
+        "second" => 1_000,
+        "millisecond" => 1,
+        _ => return None,
+    };
+    match parts.next() {
+        None => Some(value * millis),
+        Some(_) => None,
+    }
+}
+
+/// Aggregate over a frame sliding forward through a partition.
+///
+/// Rows are added when they enter the frame and retracted when they leave,
+/// NULL values are ignored.
+enum SlidingAggregate {
+    Sum {
+        sum: ScalarValue,
+        count: usize,
+    },
+    Avg {
+        sum: ScalarValue,
+        count: usize,
+    },
+    Count(i64),
+    /// Candidate rows for the minimum (`Less`) or maximum (`Greater`) in frame
+    /// order, each value is strictly better than the ones after it, so the
+    /// front is the result (monotonic deque)
+    Extremum {
+        candidates: VecDeque<usize>,
+        ordering: Ordering,
+    },
+}
+
+impl SlidingAggregate {
+    fn new(fun: &AggregateFunction) -> Self {
+        match fun {
+            AggregateFunction::Sum => Self::Sum {
+                sum: ScalarValue::Null,
+                count: 0,
+            },
+            AggregateFunction::Avg => Self::Avg {
+                sum: ScalarValue::Null,
+                count: 0,
+            },
+            AggregateFunction::Count => Self::Count(0),
+            AggregateFunction::Min => Self::Extremum {
+                candidates: VecDeque::new(),
+                ordering: Ordering::Less,
+            },
+            AggregateFunction::Max => Self::Extremum {
+                candidates: VecDeque::new(),
+                ordering: Ordering::Greater,
+            },
+            _ => unimplemented!("Aggregate window function {} is not implemented", fun),
+        }
+    }
+
+    fn add(&mut self, i: usize, values: &[ScalarValue]) -> SqcResult<()> {
+        let value = &values[i];
+        if value.is_null() {
+            return Ok(());
+        }
+        let function = self.name();
+        match self {
+            Self::Sum { sum, count } | Self::Avg { sum, count } => {
+                *sum = match sum.is_null() {
+                    true => value.clone(),
+                    false => {
+                        let (sum, value) = promote_numeric(sum, value);
+                        sum.add_checked(value)
+                            .map_err(|_| overflow(function, &sum))?
+                    }
+                };
+                *count += 1;
+            }
+            Self::Count(count) => *count += 1,
+            Self::Extremum {
+                candidates,
+                ordering,
+            } => {
+                while candidates
+                    .back()
+                    .is_some_and(|&j| compare_values(&values[j], value) != *ordering)
+                {
+                    candidates.pop_back();
+                }
+                candidates.push_back(i);
+            }
+        }
+        Ok(())
+    }
+
+    fn retract(&mut self, i: usize, values: &[ScalarValue]) -> SqcResult<()> {
+        let value = &values[i];
+        if value.is_null() {
+            return Ok(());
+        }
+        let function = self.name();
+        match self {
+            Self::Sum { sum, count } | Self::Avg { sum, count } => {
+                *count -= 1;
+                // start over from NULL so float rounding errors don't pile up
+                *sum = match count {
+                    0 => ScalarValue::Null,
+                    _ => {
+                        let (sum, value) = promote_numeric(sum, value);
+                        sum.sub_checked(value)
+                            .map_err(|_| overflow(function, &sum))?
+                    }
+                };
+            }
+            Self::Count(count) => *count -= 1,
+            Self::Extremum { candidates, .. } => {
+                if candidates.front() == Some(&i) {
+                    candidates.pop_front();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sum { .. } => "SUM",
+            Self::Avg { .. } => "AVG",
+            Self::Count(_) => "COUNT",
+            Self::Extremum {
+                ordering: Ordering::Less,
+                ..
+            } => "MIN",
+            Self::Extremum { .. } => "MAX",
+        }
+    }
+
+    fn evaluate(&self, values: &[ScalarValue]) -> ScalarValue {
+        match self {
+            Self::Sum { sum, .. } => sum.clone(),
+            Self::Avg { count: 0, .. } => ScalarValue::Float64(None),
+            Self::Avg { sum, count } => ScalarValue::from(as_f64(sum).unwrap() / *count as f64),
+            Self::Count(count) => ScalarValue::from(*count),
+            Self::Extremum { candidates, .. } => candidates
+                .front()
+                .map_or(ScalarValue::Null, |&j| values[j].clone()),
+        }
+    }
+}
+
+/// The sum of a frame doesn't fit the type of its values
+fn overflow(function: &str, sum: &ScalarValue) -> SqcError {
+    SqcError::Overflow {
+        function: function.to_string(),
+        data_type: sum.data_type(),
+    }
+}
@@ -5,12 +5,14 @@
 #[macro_use]
 extern crate log;
 
+use std::any::TypeId;
 use std::collections::BTreeMap;
+use std::ops::ControlFlow;
 use std::hash::{DefaultHasher, Hash, Hasher};
 use std::{collections::HashMap, sync::Arc};
 
 use datafusion_common::{
-    arrow::datatypes::{DataType, Field, Schema},
+    arrow::datatypes::{DataType, Field, Schema, SchemaRef, DECIMAL128_MAX_PRECISION},
     config::ConfigOptions,
     DataFusionError, ScalarValue,
 };
@@ -21,10 +23,16 @@ use datafusion_expr::{
 use datafusion_expr::{ColumnarValue, ScalarUDFImpl, Signature, Volatility};
 use datafusion_sql::{
     planner::ContextProvider,
-    sqlparser::{dialect::GenericDialect, parser::Parser},
+    sqlparser::{
+        ast::{self, visit_expressions, visit_expressions_mut, Statement},
+        dialect::{Dialect, GenericDialect},
+        keywords::Keyword,
+        parser::{Parser, ParserError},
+        tokenizer::{Token, TokenWithLocation, Tokenizer, Whitespace},
+    },
     TableReference,
 };
-use errors::SqcError;
+use errors::{SqcError, SqcResult};
 use pyo3::{
     exceptions,
     prelude::*,
@@ -34,6 +42,7 @@ use pyo3::{
 
 mod errors;
 mod executor;
+mod udaf;
 // mod logical_plan;
 // mod parser;
 // mod planner;
@@ -52,24 +61,32 @@ pub fn sqc(m: &Bound<'_, PyModule>) -> PyResult<()> {
 }
 
 #[pyfunction]
+#[pyo3(signature = (query, tables, *, max_recursion = None))]
 pub fn query<'p>(
     py: Python<'p>,
     query: &str,
     // values: Vec<Bound<'p, PyDict>>,
     tables: &Bound<'p, PyAny>,
+    max_recursion: Option<usize>,
 ) -> PyResult<Vec<Bound<'p, PyDict>>> {
-    let dialect = GenericDialect {}; // or AnsiDialect, or your own dialect ...
-    let ast = Parser::parse_sql(&dialect, query).unwrap();
-    let statement = &ast[0];
+    let dialect = SqcDialect(GenericDialect {});
+    let mut ast = parse_sql(&dialect, query).unwrap();
+    let statement = &mut ast[0];
+    check_aggregate_clauses(statement)?;
+    pass_udaf_filters_as_arguments(statement);
 
     let mut schema_provider = MyContextProvider::new();
     let mut execution_context = executor::ExecutionContext::new(py);
+    if let Some(max_recursion) = max_recursion {
+        execution_context.max_recursion = max_recursion;
+    }
 
     if !tables.is_none() {
         match tables.downcast::<PyList>() {
             Ok(values) => {
                 let dataset = values.extract::<Vec<_>>()?;
-                schema_provider.add_dataset("dataset", &dataset);
+                let schema = schema_provider.add_dataset("dataset", &dataset);
+                let dataset = rescale_decimals(dataset, &schema);
                 execution_context
                     .tables
                     .insert("dataset".to_string(), dataset);
@@ -80,7 +97,8 @@ pub fn query<'p>(
                         for item in tables.items() {
                             let name = item.get_item(0).unwrap().extract::<String>()?;
                             let dataset = item.get_item(1).unwrap().extract::<Vec<_>>()?;
-                            schema_provider.add_dataset(&name, &dataset);
+                            let schema = schema_provider.add_dataset(&name, &dataset);
+                            let dataset = rescale_decimals(dataset, &schema);
                             execution_context.tables.insert(name, dataset);
                         }
                     }
@@ -93,11 +111,52 @@ pub fn query<'p>(
     // create a logical query plan
     let sql_to_rel = datafusion_sql::planner::SqlToRel::new(&schema_provider);
     let plan = sql_to_rel.sql_statement_to_plan(statement.clone()).unwrap();
+    let plan = executor::decorrelate_predicate_subqueries(plan);
+    execution_context.shared_subquery_aliases = executor::shared_subquery_aliases(&plan);
 
     // show the plan
     debug!("{:?}", &plan);
 
-    Ok(executor::execute_plan(&plan, &execution_context))
+    let rows = executor::execute_plan(&plan, &execution_context).collect();
+    match execution_context.take_error() {
+        Some(error) => Err(error.into()),
+        None => Ok(rows),
+    }
+}
+
+/// The generic dialect, with aggregate `FILTER (WHERE ...)` clauses
+#[derive(Debug)]
+struct SqcDialect(GenericDialect);
+
+impl Dialect for SqcDialect {
+    // the parser checks the dialect by type for many constructs
+    fn dialect(&self) -> TypeId {
+        self.0.dialect()
+    }
+
+    fn is_delimited_identifier_start(&self, ch: char) -> bool {
+        self.0.is_delimited_identifier_start(ch)
+    }
+
+    fn is_identifier_start(&self, ch: char) -> bool {
+        self.0.is_identifier_start(ch)
+    }
+
+    fn is_identifier_part(&self, ch: char) -> bool {
+        self.0.is_identifier_part(ch)
+    }
+
+    fn supports_group_by_expr(&self) -> bool {
+        self.0.supports_group_by_expr()
+    }
+
+    fn supports_start_transaction_modifier(&self) -> bool {
+        self.0.supports_start_transaction_modifier()
+    }
+
+    fn supports_filter_during_aggregation(&self) -> bool {
+        true
+    }
 }
 
 pub fn infer_schema(value: &Bound<'_, PyDict>) -> Schema {
@@ -107,18 +166,189 @@ pub fn infer_schema(value: &Bound<'_, PyDict>) -> Schema {
     }
 }
 
+/// `Parser::parse_sql`, with the `WITHIN GROUP` clauses rewritten
+fn parse_sql(dialect: &SqcDialect, query: &str) -> Result<Vec<Statement>, ParserError> {
+    let tokens = Tokenizer::new(dialect, query).tokenize_with_location()?;
+    Parser::new(dialect)
+        .with_tokens_with_locations(rewrite_within_group(tokens))
+        .parse_statements()
+}
+
+/// Move the `WITHIN GROUP (ORDER BY ...)` clause of ordered-set aggregates
+/// into the function call, which the SQL parser does support:
+/// `percentile_cont(0.9) WITHIN GROUP (ORDER BY x)` becomes
+/// `percentile_cont(0.9 ORDER BY x)`.
+///
+/// The tokens are rewritten, so strings and comments are left alone.
+fn rewrite_within_group(tokens: Vec<TokenWithLocation>) -> Vec<TokenWithLocation> {
+    let mut result: Vec<TokenWithLocation> = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        let call_end = result
+            .iter()
+            .rposition(|t| !matches!(t.token, Token::Whitespace(_)));
+        if let (Some(call_end), Some((order_by, end))) = (call_end, match_within_group(&tokens, i))
+        {
+            if result[call_end].token == Token::RParen {
+                let close = result[call_end].clone();
+                result.truncate(call_end);
+                result.push(TokenWithLocation::wrap(Token::Whitespace(
+                    Whitespace::Space,
+                )));
+                result.extend_from_slice(order_by);
+                result.push(close);
+                i = end;
+                continue;
+            }
+        }
+        result.push(tokens[i].clone());
+        i += 1;
+    }
+    result
+}
+
+/// Match `WITHIN GROUP (...)` at `start`, returning the tokens inside the
+/// parentheses and the position after them
+fn match_within_group(
+    tokens: &[TokenWithLocation],
+    start: usize,
+) -> Option<(&[TokenWithLocation], usize)> {
+    let mut i = start;
+    for keyword in [Keyword::WITHIN, Keyword::GROUP] {
+        match &tokens.get(i)?.token {
+            Token::Word(word) if word.keyword == keyword => i += 1,
+            _ => return None,
+        }
+        while matches!(tokens.get(i)?.token, Token::Whitespace(_)) {
+            i += 1;
+        }
+    }
+    if tokens[i].token != Token::LParen {
+        return None;
+    }
+
+    let (open, mut depth) = (i, 0);
+    for (j, token) in tokens.iter().enumerate().skip(open) {
+        match token.token {
+            Token::LParen => depth += 1,
+            Token::RParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&tokens[open + 1..j], j + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The planner drops the `FILTER` clause of window functions and the
+/// `DISTINCT` clause of the aggregates of `udaf`, which would silently
+/// aggregate every row. It doesn't check the number of arguments of the
+/// aggregates of `udaf` either.
+fn check_aggregate_clauses(statement: &Statement) -> SqcResult<()> {
+    let result = visit_expressions(statement, |expr| {
+        if let ast::Expr::Function(function) = expr {
+            let name = function.name.to_string().to_lowercase();
+            if function.filter.is_some() && function.over.is_some() {
+                let message = "FILTER is not supported for window functions".to_string();
+                return ControlFlow::Break(SqcError::NotSupported(message));
+            }
+            if function.distinct && udaf::get_aggregate_meta(&name).is_some() {
+                let message = format!("DISTINCT is not supported for {}", name);
+                return ControlFlow::Break(SqcError::NotSupported(message));
+            }
+            match udaf::find_aggregate(&name) {
+                Some(aggregate) if function.args.len() != aggregate.arity() => {
+                    return ControlFlow::Break(SqcError::ArgumentCount {
+                        function: name,
+                        expected: aggregate.arity(),
+                        actual: function.args.len(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    match result {
+        ControlFlow::Break(error) => Err(error),
+        ControlFlow::Continue(()) => Ok(()),
+    }
+}
+
+/// The planner also drops the `FILTER` clause of the aggregates of `udaf`, so
+/// its condition is passed as an extra, last argument, marked by
+/// `udaf::FILTER_ARGUMENT`, which the executor turns back into the filter
+fn pass_udaf_filters_as_arguments(statement: &mut Statement) {
+    let _ = visit_expressions_mut(statement, |expr| {
+        if let ast::Expr::Function(function) = expr {
+            let name = function.name.to_string().to_lowercase();
+            if udaf::get_aggregate_meta(&name).is_some() {
+                if let Some(filter) = function.filter.take() {
+                    let condition = ast::FunctionArgExpr::Expr(*filter);
+                    let filter = ast::Expr::Function(ast::Function {
+                        name: ast::ObjectName(vec![ast::Ident::new(udaf::FILTER_ARGUMENT)]),
+                        args: vec![ast::FunctionArg::Unnamed(condition)],
+                        filter: None,
+                        null_treatment: None,
+                        over: None,
+                        distinct: false,
+                        special: false,
+                        order_by: vec![],
+                    });
+                    let filter = ast::FunctionArgExpr::Expr(filter);
+                    function.args.push(ast::FunctionArg::Unnamed(filter));
+                }
+            }
+        }
+        ControlFlow::<()>::Continue(())
+    });
+}
+
+/// Whether a value is a `decimal.Decimal`
+pub fn is_decimal(value: &Bound<'_, PyAny>) -> bool {
+    let decimal = value
+        .py()
+        .import_bound("decimal")
+        .unwrap()
+        .getattr("Decimal")
+        .unwrap();
+    value.is_instance(&decimal).unwrap()
+}
+
+/// Number of digits after the point of a `decimal.Decimal`
+fn decimal_scale(value: &Bound<'_, PyAny>) -> i8 {
+    let exponent = value
+        .call_method0("as_tuple")
+        .unwrap()
+        .getattr("exponent")
+        .unwrap()
+        .extract::<i64>()
+        .unwrap_or(0);
+    (-exponent).max(0) as i8
+}
+
 /// Infer the data type of a value
 pub fn infer_field_schema(value: &Bound<'_, PyAny>) -> DataType {
-    if value.is_instance_of::<types::PyInt>() {
+    if value.is_none() {
+        DataType::Null
+    } else if value.is_instance_of::<types::PyBool>() {
+        // bool is a subclass of int
+        DataType::Boolean
+    } else if value.is_instance_of::<types::PyInt>() {
         DataType::Int64
+    } else if value.is_instance_of::<types::PyFloat>() {
+        DataType::Float64
     } else if value.is_instance_of::<types::PyString>() {
         DataType::Utf8
     } else if value.is_instance_of::<types::PyBytes>() {
         DataType::Binary
-    } else if value.is_instance_of::<types::PyBool>() {
-        DataType::Boolean
     } else if value.is_instance_of::<types::PyDate>() {
         DataType::Date64
+    } else if is_decimal(value) {
+        DataType::Decimal128(DECIMAL128_MAX_PRECISION, decimal_scale(value))
     } else if value.is_instance_of::<types::PyList>() {
         DataType::new_list(infer_field_schema(&value.get_item(0).unwrap()), false)
     } else if value.is_instance_of::<types::PyDict>() {
@@ -155,12 +385,93 @@ impl MyContextProvider {
         }
     }
 
-    pub fn add_dataset(&mut self, name: &str, dataset: &Vec<Bound<'_, PyDict>>) {
+    pub fn add_dataset(&mut self, name: &str, dataset: &Vec<Bound<'_, PyDict>>) -> Schema {
         let row = dataset.first().unwrap();
+        let values = |name: &str| {
+            let name = name.to_string();
+            dataset
+                .iter()
+                .filter_map(move |row| row.get_item(&name).ok().flatten())
+        };
 
+        let fields: Vec<_> = infer_schema(row)
+            .fields()
+            .iter()
+            .map(|field| {
+                // a NULL in the first row says nothing of its column, the first value does
+                let data_type = match field.data_type() {
+                    DataType::Null => values(field.name())
+                        .find(|value| !value.is_none())
+                        .map_or(DataType::Null, |value| infer_field_schema(&value)),
+                    data_type => data_type.clone(),
+                };
+                // a decimal column has the largest scale of its values
+                let data_type = match data_type {
+                    DataType::Decimal128(precision, _) => {
+                        let scale = values(field.name())
+                            .filter(|value| is_decimal(value))
+                            .map(|value| decimal_scale(&value))
+                            .max()
+                            .unwrap_or(0);
+                        DataType::Decimal128(precision, scale)
+                    }
+                    data_type => data_type,
+                };
+                Arc::new(field.as_ref().clone().with_data_type(data_type))
+            })
+            .collect();
+
+        let schema = Schema::new(fields);
         self.tables
-            .insert(name.to_string(), create_table_source(infer_schema(row)));
+            .insert(name.to_string(), create_table_source(schema.clone()));
+        schema
+    }
+}
+
+/// Decimals of the rows with the scale of their column, so that equal values
+/// are equal keys of `GROUP BY`, `DISTINCT` and joins: `Decimal('1')` becomes
+/// `Decimal('1.00')` in a column with `Decimal('0.25')`.
+///
+/// Rows with a decimal of another scale are copied, the others are kept.
+fn rescale_decimals<'p>(
+    dataset: Vec<Bound<'p, PyDict>>,
+    schema: &Schema,
+) -> Vec<Bound<'p, PyDict>> {
+    let columns: Vec<_> = schema
+        .fields()
+        .iter()
+        .filter_map(|field| match field.data_type() {
+            DataType::Decimal128(_, scale) => Some((field.name(), *scale)),
+            _ => None,
+        })
+        .collect();
+    if columns.is_empty() {
+        return dataset;
     }
+
+    dataset
+        .into_iter()
+        .map(|row| {
+            let mut rescaled: Option<Bound<'p, PyDict>> = None;
+            for (name, scale) in &columns {
+                let value = match row.get_item(name).ok().flatten() {
+                    Some(value) if is_decimal(&value) && decimal_scale(&value) != *scale => value,
+                    _ => continue,
+                };
+                // `Decimal((0, (1,), -scale))` is `10^-scale`
+                let exponent = value
+                    .get_type()
+                    .call1(((0, (1,), -(*scale as i64)),))
+                    .unwrap();
+                let value = value.call_method1("quantize", (exponent,)).unwrap();
+                rescaled
+                    .get_or_insert_with(|| row.copy().unwrap())
+                    .set_item(name, value)
+                    .unwrap();
+            }
+            rescaled.unwrap_or(row)
+        })
+        .collect()
 }
 
 fn create_table_source(schema: Schema) -> Arc<dyn TableSource> {
@@ -178,16 +489,25 @@ impl ContextProvider for MyContextProvider {
         }
     }
 
+    fn create_cte_work_table(
+        &self,
+        _name: &str,
+        schema: SchemaRef,
+    ) -> Result<Arc<dyn TableSource>, DataFusionError> {
+        Ok(Arc::new(LogicalTableSource::new(schema)))
+    }
+
     fn get_function_meta(&self, name: &str) -> Option<Arc<ScalarUDF>> {
         let meta = match name {
             "round" => ScalarUDF::from(RoundUDF::new()),
+            udaf::FILTER_ARGUMENT => udaf::get_filter_argument_meta(),
             _ => return None,
         };
         Some(Arc::new(meta))
     }
 
-    fn get_aggregate_meta(&self, _name: &str) -> Option<Arc<AggregateUDF>> {
-        None
+    fn get_aggregate_meta(&self, name: &str) -> Option<Arc<AggregateUDF>> {
+        udaf::get_aggregate_meta(name).map(Arc::new)
     }
 
     fn get_variable_type(&self, _variable_names: &[String]) -> Option<DataType> {
@@ -207,7 +527,7 @@ impl ContextProvider for MyContextProvider {
     }
 
     fn udafs_names(&self) -> Vec<String> {
-        Vec::new()
+        udaf::aggregate_names()
     }
 
     fn udwfs_names(&self) -> Vec<String> {
@@ -232,6 +552,12 @@ impl RoundUDF {
     }
 }
 
+impl Default for RoundUDF {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn round(number: f64, rounding: i32) -> f64 {
     let scale: f64 = 10_f64.powi(rounding);
     (number * scale).round() / scale
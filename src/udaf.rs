@@ -0,0 +1,232 @@
+use std::any::Any;
+
+use datafusion_common::{
+    arrow::datatypes::{DataType, Fields},
+    not_impl_err, DataFusionError,
+};
+use datafusion_expr::{
+    expr, function::AccumulatorArgs, AggregateUDF, AggregateUDFImpl, ColumnarValue, ScalarUDF,
+    ScalarUDFImpl, Signature, Volatility,
+};
+
+use crate::executor::{standard, Accumulator};
+
+mod weighted_avg;
+
+/// An aggregate function evaluated by the executor.
+///
+/// Adding an implementation to `AGGREGATES` makes it known to the planner,
+/// which gives it precedence over a DataFusion built-in of the same name, and
+/// drops its `DISTINCT` clause: aggregates that DataFusion has are evaluated
+/// as built-ins instead.
+pub trait Aggregate: Sync {
+    fn name(&self) -> &str;
+
+    /// Number of arguments, of any type
+    fn arity(&self) -> usize;
+
+    fn return_type(&self, arg_types: &[DataType]) -> DataType;
+
+    /// Whether the accumulator also gets the values of the `ORDER BY`
+    /// expressions of the call, after the arguments
+    fn is_order_sensitive(&self) -> bool {
+        false
+    }
+
+    /// State for one group
+    fn accumulator(
+        &self,
+        aggfunc: &expr::AggregateFunction,
+        arg_types: &[DataType],
+    ) -> Box<dyn Accumulator>;
+}
+
+static AGGREGATES: &[&dyn Aggregate] = &[
+    &Standard::new("variance", 1, float64, standard::variance),
+    &Standard::new("percentile_cont", 1, float64, standard::percentile_cont).order_sensitive(),
+    &Standard::new("percentile_disc", 1, float64, standard::percentile_disc).order_sensitive(),
+    &Standard::new("covar", 2, float64, standard::covar_samp),
+    &Standard::new("covar_samp", 2, float64, standard::covar_samp),
+    &Standard::new("json_object_agg", 2, keys, standard::json_object_agg).order_sensitive(),
+    &Standard::new("max_by", 2, first_argument, standard::max_by),
+    &Standard::new("min_by", 2, first_argument, standard::min_by),
+    &Standard::new("every", 1, boolean, standard::every),
+    &Standard::new("any_value", 1, first_argument, standard::any_value),
+    &weighted_avg::WeightedAvg,
+];
+
+pub fn find_aggregate(name: &str) -> Option<&'static dyn Aggregate> {
+    AGGREGATES.iter().copied().find(|a| a.name() == name)
+}
+
+pub fn aggregate_names() -> Vec<String> {
+    AGGREGATES.iter().map(|a| a.name().to_string()).collect()
+}
+
+pub fn get_aggregate_meta(name: &str) -> Option<AggregateUDF> {
+    let aggregate = find_aggregate(name)?;
+    Some(AggregateUDF::from(AggregateMeta {
+        aggregate,
+        signature: Signature::any(aggregate.arity(), Volatility::Immutable),
+    }))
+}
+
+/// Aggregates of the SQL standard and of common databases, their accumulators
+/// are part of the executor
+struct Standard {
+    name: &'static str,
+    arity: usize,
+    return_type: fn(&[DataType]) -> DataType,
+    order_sensitive: bool,
+    accumulator: fn(&expr::AggregateFunction) -> Box<dyn Accumulator>,
+}
+
+impl Standard {
+    const fn new(
+        name: &'static str,
+        arity: usize,
+        return_type: fn(&[DataType]) -> DataType,
+        accumulator: fn(&expr::AggregateFunction) -> Box<dyn Accumulator>,
+    ) -> Self {
+        Self {
+            name,
+            arity,
+            return_type,
+            order_sensitive: false,
+            accumulator,
+        }
+    }
+
+    /// See `Aggregate::is_order_sensitive`
+    const fn order_sensitive(self) -> Self {
+        Self {
+            order_sensitive: true,
+            ..self
+        }
+    }
+}
+
+impl Aggregate for Standard {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> DataType {
+        (self.return_type)(arg_types)
+    }
+
+    fn is_order_sensitive(&self) -> bool {
+        self.order_sensitive
+    }
+
+    fn accumulator(
+        &self,
+        aggfunc: &expr::AggregateFunction,
+        _arg_types: &[DataType],
+    ) -> Box<dyn Accumulator> {
+        (self.accumulator)(aggfunc)
+    }
+}
+
+/// Also the type of PERCENTILE_DISC, which returns a value of its `WITHIN
+/// GROUP` expression: that type isn't known here, numbers are the common case
+fn float64(_arg_types: &[DataType]) -> DataType {
+    DataType::Float64
+}
+
+fn boolean(_arg_types: &[DataType]) -> DataType {
+    DataType::Boolean
+}
+
+fn first_argument(arg_types: &[DataType]) -> DataType {
+    arg_types[0].clone()
+}
+
+/// A struct whose fields are the aggregated keys
+fn keys(_arg_types: &[DataType]) -> DataType {
+    DataType::Struct(Fields::empty())
+}
+
+/// Function that marks the `FILTER` condition of an aggregate of `udaf`,
+/// which the query passes as an extra, last argument
+pub const FILTER_ARGUMENT: &str = "__aggregate_filter";
+
+pub fn get_filter_argument_meta() -> ScalarUDF {
+    ScalarUDF::from(FilterArgument {
+        signature: Signature::any(1, Volatility::Immutable),
+    })
+}
+
+/// Planning information of `FILTER_ARGUMENT`, the executor takes it out of
+/// the arguments and evaluates the condition as the filter
+#[derive(Debug)]
+struct FilterArgument {
+    signature: Signature,
+}
+
+impl ScalarUDFImpl for FilterArgument {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        FILTER_ARGUMENT
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType, DataFusionError> {
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke(&self, _args: &[ColumnarValue]) -> Result<ColumnarValue, DataFusionError> {
+        not_impl_err!("{} is evaluated by the executor", self.name())
+    }
+}
+
+/// Planning information of an aggregate function, which is evaluated by the
+/// executor and not by DataFusion
+struct AggregateMeta {
+    aggregate: &'static dyn Aggregate,
+    signature: Signature,
+}
+
+impl std::fmt::Debug for AggregateMeta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AggregateMeta")
+            .field("name", &self.aggregate.name())
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+impl AggregateUDFImpl for AggregateMeta {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.aggregate.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType, DataFusionError> {
+        Ok(self.aggregate.return_type(arg_types))
+    }
+
+    fn accumulator(
+        &self,
+        _acc_args: AccumulatorArgs,
+    ) -> Result<Box<dyn datafusion_expr::Accumulator>, DataFusionError> {
+        not_impl_err!("{} is evaluated by the executor", self.name())
+    }
+}
@@ -0,0 +1,61 @@
+use datafusion_common::{arrow::datatypes::DataType, ScalarValue};
+use datafusion_expr::expr;
+
+use crate::executor::{as_f64, Accumulator};
+
+use super::Aggregate;
+
+/// `WEIGHTED_AVG(value, weight)`: `SUM(value * weight) / SUM(weight)`, rows
+/// where either is NULL are ignored
+pub struct WeightedAvg;
+
+impl Aggregate for WeightedAvg {
+    fn name(&self) -> &str {
+        "weighted_avg"
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> DataType {
+        DataType::Float64
+    }
+
+    fn accumulator(
+        &self,
+        _aggfunc: &expr::AggregateFunction,
+        _arg_types: &[DataType],
+    ) -> Box<dyn Accumulator> {
+        Box::<WeightedAvgAccumulator>::default()
+    }
+}
+
+#[derive(Default)]
+struct WeightedAvgAccumulator {
+    weighted_sum: f64,
+    weights: f64,
+}
+
+impl Accumulator for WeightedAvgAccumulator {
+    fn update(&mut self, values: &[ScalarValue]) {
+        if let (Some(value), Some(weight)) = (as_f64(&values[0]), as_f64(&values[1])) {
+            self.weighted_sum += value * weight;
+            self.weights += weight;
+        }
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) {
+        let other = other.downcast_ref::<Self>();
+        self.weighted_sum += other.weighted_sum;
+        self.weights += other.weights;
+    }
+
+    fn evaluate(&self) -> ScalarValue {
+        // no rows, or weights that cancel out
+        if self.weights == 0.0 {
+            return ScalarValue::Float64(None);
+        }
+        ScalarValue::Float64(Some(self.weighted_sum / self.weights))
+    }
+}
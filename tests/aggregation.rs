@@ -1,4 +1,4 @@
-use pyo3::{types::PyList, Py, Python};
+use pyo3::{exceptions::PyRuntimeError, prelude::PyAnyMethods, types::PyList, Py, Python};
 use rstest::*;
 
 mod utils;
@@ -12,8 +12,369 @@ fn test_agg_query(ad_data: &Py<PyList>) {
     WHERE campaign_id = 10
     "#;
     Python::with_gil(|py| {
-        let result = sqc::query(py, query, ad_data.bind(py)).unwrap();
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
         let expected = vec![pydict!(py, "Spend" => 30)];
         pyo3::py_run!(py, result expected, r#"assert result == expected"#);
     });
 }
+
+#[rstest]
+fn test_having(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT campaign_id, sum(spend)
+    FROM dataset
+    GROUP BY campaign_id
+    HAVING count(*) > 1
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![pydict!(py, "campaign_id" => 10, "SUM(dataset.spend)" => 30)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_having_aggregate_not_selected(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT campaign_id
+    FROM dataset
+    GROUP BY campaign_id
+    HAVING sum(spend) > 25 AND count(*) = 1
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![pydict!(py, "campaign_id" => 20)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_having_qualified_group_keys(ad_data: &Py<PyList>, campaign_data: &Py<PyList>) {
+    let query = r#"
+    SELECT ad_data.id AS ad_id, campaign_data.id AS campaign_id, sum(spend) AS total
+    FROM ad_data
+    JOIN campaign_data ON ad_data.campaign_id = campaign_data.id
+    GROUP BY ad_data.id, campaign_data.id
+    HAVING campaign_data.id = 10 AND sum(spend) > 10
+    "#;
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign_data" => campaign_data);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![pydict!(py, "ad_id" => 2, "campaign_id" => 10, "total" => 20)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_aggregate_functions() {
+    let query = r#"
+    SELECT
+        campaign_id,
+        count(*) AS ads,
+        count(DISTINCT day) AS days,
+        avg(spend) AS avg_spend,
+        min(spend) AS min_spend,
+        max(day) AS last_day
+    FROM dataset
+    GROUP BY campaign_id
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "campaign_id" => 20, "day" => "mon", "spend" => 5),
+                pydict!(py, "campaign_id" => 10, "day" => "mon", "spend" => 10),
+                pydict!(py, "campaign_id" => 10, "day" => "tue", "spend" => 20),
+                pydict!(py, "campaign_id" => 10, "day" => "mon", "spend" => None::<i64>),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        // groups come out in the order they are first seen
+        let expected = vec![
+            pydict!(py, "campaign_id" => 20, "ads" => 1, "days" => 1, "avg_spend" => 5.0, "min_spend" => 5, "last_day" => "mon"),
+            pydict!(py, "campaign_id" => 10, "ads" => 3, "days" => 2, "avg_spend" => 15.0, "min_spend" => 10, "last_day" => "tue"),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_aggregate_without_rows(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT count(*) AS ads, max(spend) AS max_spend
+    FROM dataset
+    WHERE campaign_id = 0
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![pydict!(py, "ads" => 0, "max_spend" => None::<i64>)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_sum_types() {
+    let query = r#"
+    SELECT
+        campaign_id,
+        sum(clicks) AS clicks,
+        sum(ctr) AS ctr,
+        sum(cost) AS cost
+    FROM dataset
+    GROUP BY campaign_id
+    "#;
+    Python::with_gil(|py| {
+        let decimal = |s: &str| {
+            py.import_bound("decimal")
+                .unwrap()
+                .getattr("Decimal")
+                .unwrap()
+                .call1((s,))
+                .unwrap()
+        };
+        let none = None::<i64>;
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "campaign_id" => 10, "clicks" => 1, "ctr" => 0.25, "cost" => decimal("1.10")),
+                pydict!(py, "campaign_id" => 10, "clicks" => 2, "ctr" => 0.5, "cost" => decimal("2.5")),
+                pydict!(py, "campaign_id" => 20, "clicks" => none, "ctr" => none, "cost" => none),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "campaign_id" => 10, "clicks" => 3, "ctr" => 0.75, "cost" => decimal("3.60")),
+            pydict!(py, "campaign_id" => 20, "clicks" => none, "ctr" => none, "cost" => none),
+        ];
+        pyo3::py_run!(py, result expected, r#"
+assert result == expected
+assert [type(row["clicks"]) for row in result] == [int, type(None)]
+assert type(result[0]["ctr"]) is float
+assert str(result[0]["cost"]) == "3.60"
+"#);
+    });
+}
+
+#[rstest]
+fn test_sum_mixed_types() {
+    let query = r#"
+    SELECT
+        sum(clicks) AS clicks,
+        sum(cost) AS cost
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let decimal = |s: &str| {
+            py.import_bound("decimal")
+                .unwrap()
+                .getattr("Decimal")
+                .unwrap()
+                .call1((s,))
+                .unwrap()
+        };
+        // the types of the columns come from their first value, not the first row
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "clicks" => None::<i64>, "cost" => decimal("2.5")),
+                pydict!(py, "clicks" => 1, "cost" => None::<i64>),
+                pydict!(py, "clicks" => 2.75, "cost" => decimal("1.15")),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![pydict!(py, "clicks" => 3.75, "cost" => decimal("3.65"))];
+        pyo3::py_run!(py, result expected, r#"
+assert result == expected
+assert str(result[0]["cost"]) == "3.65"
+"#);
+    });
+}
+
+#[rstest]
+fn test_group_by_decimals_of_different_scales() {
+    let query = r#"
+    SELECT price, count(*) AS n, approx_distinct(price) AS distinct_prices
+    FROM dataset
+    GROUP BY price
+    "#;
+    Python::with_gil(|py| {
+        let decimal = |s: &str| {
+            py.import_bound("decimal")
+                .unwrap()
+                .getattr("Decimal")
+                .unwrap()
+                .call1((s,))
+                .unwrap()
+        };
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "price" => decimal("1")),
+                pydict!(py, "price" => decimal("1.0")),
+                pydict!(py, "price" => decimal("1.00")),
+                pydict!(py, "price" => decimal("2.5")),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "price" => decimal("1.00"), "n" => 3, "distinct_prices" => 1),
+            pydict!(py, "price" => decimal("2.50"), "n" => 1, "distinct_prices" => 1),
+        ];
+        pyo3::py_run!(
+            py,
+            result expected,
+            r#"
+assert result == expected
+assert [str(row["price"]) for row in result] == ["1.00", "2.50"]
+"#
+        );
+    });
+}
+
+#[rstest]
+fn test_sum_overflow() {
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![pydict!(py, "value" => i64::MAX), pydict!(py, "value" => 1)],
+        );
+        let error = sqc::query(py, "SELECT sum(value) FROM dataset", &data, None).unwrap_err();
+        assert!(error.is_instance_of::<PyRuntimeError>(py));
+        assert!(error
+            .to_string()
+            .contains(r#"Overflow { function: "SUM", data_type: Int64 }"#));
+    });
+}
+
+#[rstest]
+fn test_aggregate_filter(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT
+        campaign_id,
+        sum(spend) FILTER (WHERE id > 1) AS late_spend,
+        count(*) FILTER (WHERE spend >= 20) AS big,
+        array_agg(id ORDER BY spend DESC) FILTER (WHERE spend < 30) AS ids
+    FROM dataset
+    GROUP BY campaign_id
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "campaign_id" => 10, "late_spend" => 20, "big" => 1, "ids" => vec![2, 1]),
+            pydict!(py, "campaign_id" => 20, "late_spend" => 30, "big" => 1, "ids" => None::<Vec<i64>>),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_filter_on_executor_aggregates(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT
+        weighted_avg(spend, id) FILTER (WHERE id > 1) AS late_spend,
+        max_by(id, spend) AS top,
+        max_by(id, spend) FILTER (WHERE spend < 30) AS top_small,
+        percentile_cont(0.5) WITHIN GROUP (ORDER BY spend) FILTER (WHERE campaign_id = 10) AS median
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected =
+            vec![pydict!(py, "late_spend" => 26.0, "top" => 3, "top_small" => 2, "median" => 15.0)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_max_by_and_boolean_aggregates() {
+    let query = r#"
+    SELECT
+        day,
+        max_by(campaign, spend) AS top,
+        min_by(campaign, spend) AS bottom,
+        bool_and(active) AS all_active,
+        bool_or(active) AS any_active,
+        bool_and(active) FILTER (WHERE spend < 20) AS all_cheap_active,
+        bool_or(active) FILTER (WHERE spend > 20) AS any_costly_active,
+        every(day = 1) AS every_first_day,
+        any_value(campaign) AS some_campaign
+    FROM dataset
+    GROUP BY day
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "day" => 1, "campaign" => "a", "spend" => 10, "active" => true),
+                pydict!(py, "day" => 1, "campaign" => "b", "spend" => 30, "active" => false),
+                pydict!(py, "day" => 1, "campaign" => "c", "spend" => None::<i64>, "active" => None::<bool>),
+                pydict!(py, "day" => 2, "campaign" => "c", "spend" => 5, "active" => true),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "day" => 1, "top" => "b", "bottom" => "a", "all_active" => false, "any_active" => true, "all_cheap_active" => true, "any_costly_active" => false, "every_first_day" => true, "some_campaign" => "a"),
+            pydict!(py, "day" => 2, "top" => "c", "bottom" => "c", "all_active" => true, "any_active" => true, "all_cheap_active" => true, "any_costly_active" => None::<bool>, "every_first_day" => false, "some_campaign" => "c"),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+#[case("SELECT variance(spend, id) FROM dataset", "variance", 1, 2)]
+#[case("SELECT max_by(id) FROM dataset", "max_by", 2, 1)]
+#[case(
+    "SELECT weighted_avg(spend, id, campaign_id) FROM dataset",
+    "weighted_avg",
+    2,
+    3
+)]
+#[case(
+    "SELECT weighted_avg(spend, id, id > 1) FILTER (WHERE id > 2) FROM dataset",
+    "weighted_avg",
+    2,
+    3
+)]
+fn test_executor_aggregate_argument_count(
+    ad_data: &Py<PyList>,
+    #[case] query: &str,
+    #[case] function: &str,
+    #[case] expected: usize,
+    #[case] actual: usize,
+) {
+    Python::with_gil(|py| {
+        let error = sqc::query(py, query, ad_data.bind(py), None).unwrap_err();
+        assert!(error.is_instance_of::<PyRuntimeError>(py));
+        let message = format!(
+            r#"ArgumentCount {{ function: "{}", expected: {}, actual: {} }}"#,
+            function, expected, actual
+        );
+        assert!(error.to_string().contains(&message));
+    });
+}
+
+#[rstest]
+fn test_weighted_avg() {
+    let query = r#"
+    SELECT campaign, weighted_avg(ctr, spend) AS ctr
+    FROM dataset
+    GROUP BY campaign
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "campaign" => "a", "ctr" => 0.5, "spend" => 10.0),
+                pydict!(py, "campaign" => "a", "ctr" => 0.25, "spend" => 30.0),
+                pydict!(py, "campaign" => "a", "ctr" => None::<f64>, "spend" => 100.0),
+                pydict!(py, "campaign" => "b", "ctr" => 0.75, "spend" => 0.0),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "campaign" => "a", "ctr" => 0.3125),
+            pydict!(py, "campaign" => "b", "ctr" => None::<f64>),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
@@ -0,0 +1,90 @@
+use pyo3::{types::*, Py, Python};
+use rstest::*;
+
+mod utils;
+
+use utils::*;
+
+#[rstest]
+fn test_searched_case(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT
+        id,
+        CASE
+            WHEN spend >= 30 THEN 'high'
+            WHEN spend >= 20 THEN 'medium'
+            WHEN spend >= 10 THEN 'low'
+        END AS bucket,
+        CASE WHEN spend > 100 THEN 'huge' END AS huge
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "bucket" => "low", "huge" => None::<&str>),
+            pydict!(py, "id" => 2, "bucket" => "medium", "huge" => None::<&str>),
+            pydict!(py, "id" => 3, "bucket" => "high", "huge" => None::<&str>),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_simple_case(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT id, CASE campaign_id WHEN 10 THEN 'search' WHEN 20 THEN 'display' ELSE 'other' END AS channel
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "channel" => "search"),
+            pydict!(py, "id" => 2, "channel" => "search"),
+            pydict!(py, "id" => 3, "channel" => "display"),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_simple_case_compares_numbers_numerically(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT id, CASE spend WHEN 10.0 THEN 'ten' WHEN 2.0e1 THEN 'twenty' ELSE 'other' END AS amount
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "amount" => "ten"),
+            pydict!(py, "id" => 2, "amount" => "twenty"),
+            pydict!(py, "id" => 3, "amount" => "other"),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_case_evaluates_only_selected_branch() {
+    let query = r#"
+    SELECT
+        CASE WHEN clicks > 0 THEN spend / clicks ELSE 0 END AS cpc,
+        CASE WHEN clicks >= 0 THEN 1 WHEN spend / clicks > 0 THEN 2 END AS first,
+        CASE clicks WHEN NULL THEN 'null' ELSE 'not equal' END AS null_operand
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "spend" => 30, "clicks" => 3),
+                pydict!(py, "spend" => 10, "clicks" => 0),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "cpc" => 10, "first" => 1, "null_operand" => "not equal"),
+            pydict!(py, "cpc" => 0, "first" => 1, "null_operand" => "not equal"),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
@@ -0,0 +1,149 @@
+use pyo3::{prelude::PyAnyMethods, types::PyList, Py, Python};
+use rstest::*;
+
+mod utils;
+
+#[fixture]
+fn people() -> Py<PyList> {
+    Python::with_gil(|py| {
+        PyList::new_bound(
+            py,
+            [
+                pydict!(py, "team" => "a", "name" => "Carol", "age" => 41),
+                pydict!(py, "team" => "a", "name" => "Alice", "age" => 29),
+                pydict!(py, "team" => "b", "name" => "Bob", "age" => 35),
+                pydict!(py, "team" => "a", "name" => None::<&str>, "age" => 33),
+            ],
+        )
+        .unbind()
+    })
+}
+
+#[rstest]
+fn test_array_agg(people: Py<PyList>) {
+    let query = r#"
+    SELECT
+        team,
+        array_agg(name ORDER BY age) AS names,
+        array_agg(age ORDER BY age DESC) AS ages,
+        array_agg(name) AS unordered
+    FROM dataset
+    GROUP BY team
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, people.bind(py), None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"
+a, b = result
+assert a == {"team": "a", "names": ["Alice", None, "Carol"], "ages": [41, 33, 29], "unordered": ["Carol", "Alice", None]}
+assert b == {"team": "b", "names": ["Bob"], "ages": [35], "unordered": ["Bob"]}
+"#
+        );
+    });
+}
+
+#[rstest]
+fn test_array_agg_of_mixed_numbers() {
+    let query = r#"
+    SELECT array_agg(price ORDER BY id) AS prices, array_agg(quantity ORDER BY id) AS quantities
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let decimal = |s: &str| {
+            py.import_bound("decimal")
+                .unwrap()
+                .getattr("Decimal")
+                .unwrap()
+                .call1((s,))
+                .unwrap()
+        };
+        let data = PyList::new_bound(
+            py,
+            [
+                pydict!(py, "id" => 1, "price" => decimal("1.5"), "quantity" => 1),
+                pydict!(py, "id" => 2, "price" => decimal("2"), "quantity" => 2.5),
+                pydict!(py, "id" => 3, "price" => None::<i64>, "quantity" => None::<i64>),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"
+[row] = result
+assert [str(price) for price in row["prices"]] == ["1.5", "2.0", "None"]
+assert row["quantities"] == [1.0, 2.5, None]
+assert isinstance(row["quantities"][0], float)
+"#
+        );
+    });
+}
+
+#[rstest]
+fn test_string_agg(people: Py<PyList>) {
+    let query = r#"
+    SELECT team, string_agg(name, ', ' ORDER BY name) AS names
+    FROM dataset
+    GROUP BY team
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, people.bind(py), None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"
+assert result == [{"team": "a", "names": "Alice, Carol"}, {"team": "b", "names": "Bob"}]
+"#
+        );
+    });
+}
+
+#[rstest]
+fn test_json_object_agg() {
+    let query = r#"
+    SELECT host, json_object_agg(metric, value) AS metrics
+    FROM dataset
+    GROUP BY host
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            [
+                pydict!(py, "host" => "web", "metric" => "cpu", "value" => 0.5),
+                pydict!(py, "host" => "web", "metric" => "mem", "value" => 0.25),
+                pydict!(py, "host" => "db", "metric" => "cpu", "value" => 0.75),
+                pydict!(py, "host" => "web", "metric" => "cpu", "value" => 0.125),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"
+assert result == [
+    {"host": "web", "metrics": {"cpu": 0.125, "mem": 0.25}},
+    {"host": "db", "metrics": {"cpu": 0.75}},
+]
+"#
+        );
+    });
+}
+
+#[rstest]
+fn test_collecting_aggregates_without_rows(people: Py<PyList>) {
+    let query = r#"
+    SELECT array_agg(name) AS names, string_agg(name, ',') AS joined
+    FROM dataset
+    WHERE age > 100
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, people.bind(py), None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"assert result == [{"names": None, "joined": None}]"#
+        );
+    });
+}
@@ -0,0 +1,144 @@
+use pyo3::{
+    prelude::PyAnyMethods,
+    types::{PyList, PyModule},
+    Py, Python,
+};
+use rstest::*;
+
+mod utils;
+use utils::*;
+
+#[rstest]
+fn test_cte_chain(ad_data: &Py<PyList>) {
+    let query = r#"
+    WITH
+        big AS (SELECT id, campaign_id FROM dataset WHERE spend > 10),
+        campaigns AS (SELECT DISTINCT campaign_id FROM big)
+    SELECT campaign_id FROM campaigns
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "campaign_id" => 10),
+            pydict!(py, "campaign_id" => 20),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_cte_referenced_twice(ad_data: &Py<PyList>) {
+    let query = r#"
+    WITH ads AS (SELECT id, campaign_id FROM dataset)
+    SELECT a.id AS a_id, b.id AS b_id
+    FROM ads a
+    JOIN ads b ON a.campaign_id = b.campaign_id AND a.id < b.id
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![pydict!(py, "a_id" => 1, "b_id" => 2)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_cte_referenced_twice_runs_once() {
+    Python::with_gil(|py| {
+        // reading a `Spend` value calls its `as_tuple`, which counts the table scans
+        let spend = PyModule::from_code_bound(
+            py,
+            r#"
+from decimal import Decimal
+
+calls = 0
+
+class Spend(Decimal):
+    def as_tuple(self):
+        global calls
+        calls += 1
+        return super().as_tuple()
+"#,
+            "spend.py",
+            "spend",
+        )
+        .unwrap();
+        let data: Vec<_> = (1..=3)
+            .map(|id| {
+                let value = spend.getattr("Spend").unwrap().call1((id * 10,)).unwrap();
+                pydict!(py, "id" => id, "spend" => value)
+            })
+            .collect();
+        let data = PyList::new_bound(py, data);
+        let scan_calls = |query: &str| {
+            spend.setattr("calls", 0).unwrap();
+            sqc::query(py, query, &data, None).unwrap();
+            spend.getattr("calls").unwrap().extract::<i64>().unwrap()
+        };
+
+        let once = scan_calls("WITH s AS (SELECT id, spend FROM dataset) SELECT id FROM s");
+        let twice = scan_calls(
+            r#"
+            WITH s AS (SELECT id, spend FROM dataset)
+            SELECT a.id FROM s a JOIN s b ON a.spend = b.spend
+            "#,
+        );
+        assert_eq!(once, twice);
+    });
+}
+
+#[rstest]
+fn test_cte_in_correlated_subquery(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT
+        a.id,
+        (
+            SELECT count(*) FROM (
+                WITH c AS (SELECT id FROM dataset b WHERE b.id <= a.id)
+                SELECT c1.id FROM c c1 JOIN c c2 ON c1.id = c2.id
+            ) s
+        ) AS n
+    FROM dataset a
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "n" => 1),
+            pydict!(py, "id" => 2, "n" => 2),
+            pydict!(py, "id" => 3, "n" => 3),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_derived_table(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT t.total * 2 AS double_total
+    FROM (SELECT sum(spend) AS total FROM dataset) AS t
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![pydict!(py, "double_total" => 120)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_self_join_many_to_many(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT a.id AS a_id, b.id AS b_id
+    FROM dataset a
+    JOIN dataset b ON a.campaign_id = b.campaign_id
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "a_id" => 1, "b_id" => 1),
+            pydict!(py, "a_id" => 1, "b_id" => 2),
+            pydict!(py, "a_id" => 2, "b_id" => 1),
+            pydict!(py, "a_id" => 2, "b_id" => 2),
+            pydict!(py, "a_id" => 3, "b_id" => 3),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
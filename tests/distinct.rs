@@ -0,0 +1,38 @@
+use pyo3::{types::PyList, Py, Python};
+use rstest::*;
+
+mod utils;
+use utils::*;
+
+#[rstest]
+fn test_select_distinct(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT DISTINCT campaign_id
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "campaign_id" => 10),
+            pydict!(py, "campaign_id" => 20),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_select_distinct_on(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT DISTINCT ON (campaign_id) campaign_id, id
+    FROM dataset
+    ORDER BY campaign_id, spend DESC
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "campaign_id" => 10, "id" => 2),
+            pydict!(py, "campaign_id" => 20, "id" => 3),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
@@ -0,0 +1,132 @@
+use pyo3::{types::PyList, Py, Python};
+use rstest::*;
+
+mod utils;
+use utils::*;
+
+#[rstest]
+fn test_rollup(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT campaign_id, id, sum(spend) AS spend, GROUPING(campaign_id) AS gc, GROUPING(id) AS gi
+    FROM dataset
+    GROUP BY ROLLUP(campaign_id, id)
+    ORDER BY campaign_id, id
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "campaign_id" => 10, "id" => 1, "spend" => 10, "gc" => 0, "gi" => 0),
+            pydict!(py, "campaign_id" => 10, "id" => 2, "spend" => 20, "gc" => 0, "gi" => 0),
+            pydict!(py, "campaign_id" => 10, "id" => None::<i64>, "spend" => 30, "gc" => 0, "gi" => 1),
+            pydict!(py, "campaign_id" => 20, "id" => 3, "spend" => 30, "gc" => 0, "gi" => 0),
+            pydict!(py, "campaign_id" => 20, "id" => None::<i64>, "spend" => 30, "gc" => 0, "gi" => 1),
+            pydict!(py, "campaign_id" => None::<i64>, "id" => None::<i64>, "spend" => 60, "gc" => 1, "gi" => 1),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_cube() {
+    let query = r#"
+    SELECT day, campaign_id, sum(spend) AS spend, GROUPING(day) AS gd, GROUPING(campaign_id) AS gc
+    FROM dataset
+    GROUP BY CUBE(day, campaign_id)
+    ORDER BY gd, gc, day, campaign_id
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "day" => "mon", "campaign_id" => 10, "spend" => 10),
+                pydict!(py, "day" => "tue", "campaign_id" => 10, "spend" => 20),
+                pydict!(py, "day" => "mon", "campaign_id" => 20, "spend" => 30),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let none = None::<i64>;
+        let expected = vec![
+            pydict!(py, "day" => "mon", "campaign_id" => 10, "spend" => 10, "gd" => 0, "gc" => 0),
+            pydict!(py, "day" => "mon", "campaign_id" => 20, "spend" => 30, "gd" => 0, "gc" => 0),
+            pydict!(py, "day" => "tue", "campaign_id" => 10, "spend" => 20, "gd" => 0, "gc" => 0),
+            pydict!(py, "day" => "mon", "campaign_id" => none, "spend" => 40, "gd" => 0, "gc" => 1),
+            pydict!(py, "day" => "tue", "campaign_id" => none, "spend" => 20, "gd" => 0, "gc" => 1),
+            pydict!(py, "day" => none, "campaign_id" => 10, "spend" => 30, "gd" => 1, "gc" => 0),
+            pydict!(py, "day" => none, "campaign_id" => 20, "spend" => 30, "gd" => 1, "gc" => 0),
+            pydict!(py, "day" => none, "campaign_id" => none, "spend" => 60, "gd" => 1, "gc" => 1),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_grouping_sets_keep_null_keys_apart() {
+    let query = r#"
+    SELECT campaign_id, sum(spend) AS spend, GROUPING(campaign_id) AS g
+    FROM dataset
+    GROUP BY GROUPING SETS ((campaign_id), ())
+    ORDER BY g, campaign_id
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "campaign_id" => None::<i64>, "spend" => 5),
+                pydict!(py, "campaign_id" => 10, "spend" => 7),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "campaign_id" => 10, "spend" => 7, "g" => 0),
+            pydict!(py, "campaign_id" => None::<i64>, "spend" => 5, "g" => 0),
+            pydict!(py, "campaign_id" => None::<i64>, "spend" => 12, "g" => 1),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_rollup_subtotals_merge_groups() {
+    let aggregates = r#"
+        count(*) AS n,
+        count(DISTINCT user_id) AS users,
+        approx_distinct(user_id) AS approx_users,
+        avg(latency) AS avg,
+        stddev(latency) AS stddev,
+        approx_percentile_cont(latency, 0.5) AS p50,
+        array_agg(DISTINCT day ORDER BY day) AS days
+    "#;
+    let rollup = format!(
+        "SELECT region, day, {} FROM dataset GROUP BY ROLLUP(region, day)",
+        aggregates
+    );
+    let by_region = format!("SELECT region, {} FROM dataset GROUP BY region", aggregates);
+    let total = format!("SELECT {} FROM dataset", aggregates);
+    Python::with_gil(|py| {
+        let data: Vec<_> = (0..3000)
+            .map(|i: i64| {
+                pydict!(py, "region" => i % 3, "day" => i % 7, "user_id" => i % 700, "latency" => i % 1000)
+            })
+            .collect();
+        let data = PyList::new_bound(py, data);
+        let rollup = sqc::query(py, &rollup, &data, None).unwrap();
+        let by_region = sqc::query(py, &by_region, &data, None).unwrap();
+        let total = sqc::query(py, &total, &data, None).unwrap();
+        pyo3::py_run!(py, rollup by_region total, r#"
+def check(merged, expected):
+    from math import isclose
+    assert merged["n"] == expected["n"] and merged["users"] == expected["users"]
+    # the sketch of a union is the union of the sketches
+    assert merged["approx_users"] == expected["approx_users"]
+    assert isclose(merged["avg"], expected["avg"]) and isclose(merged["stddev"], expected["stddev"])
+    assert abs(merged["p50"] - expected["p50"]) <= 10, (merged["p50"], expected["p50"])
+    assert merged["days"] == expected["days"] == list(range(7))
+
+subtotals = {row["region"]: row for row in rollup if row["day"] is None}
+assert len(subtotals) == 4
+for expected in by_region:
+    check(subtotals[expected["region"]], expected)
+check(subtotals[None], total[0])
+"#);
+    });
+}
@@ -0,0 +1,182 @@
+use pyo3::{types::PyList, Py, Python};
+use rstest::*;
+
+mod utils;
+use utils::*;
+
+#[rstest]
+#[case("JOIN", vec![(Some(1), Some(10)), (Some(2), Some(10))])]
+#[case("LEFT JOIN", vec![(Some(1), Some(10)), (Some(2), Some(10)), (Some(3), None)])]
+#[case("RIGHT JOIN", vec![(Some(1), Some(10)), (Some(2), Some(10)), (None, Some(30))])]
+#[case(
+    "FULL JOIN",
+    vec![(Some(1), Some(10)), (Some(2), Some(10)), (Some(3), None), (None, Some(30))]
+)]
+fn test_join_types(
+    ad_data: &Py<PyList>,
+    campaign_data: &Py<PyList>,
+    #[case] join: &str,
+    #[case] expected: Vec<(Option<i64>, Option<i64>)>,
+) {
+    let query = format!(
+        r#"
+        SELECT ad_data.id AS ad_id, campaign_data.id AS campaign_id
+        FROM ad_data
+        {} campaign_data ON ad_data.campaign_id = campaign_data.id
+        "#,
+        join
+    );
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign_data" => campaign_data);
+        let result = sqc::query(py, &query, &tables, None).unwrap();
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(ad_id, campaign_id)| pydict!(py, "ad_id" => ad_id, "campaign_id" => campaign_id))
+            .collect();
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_join_one_to_many(ad_data: &Py<PyList>, campaign_data: &Py<PyList>) {
+    let query = r#"
+    SELECT campaign_data.name, ad_data.id
+    FROM campaign_data
+    JOIN ad_data ON campaign_data.id = ad_data.campaign_id
+    "#;
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign_data" => campaign_data);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![
+            pydict!(py, "name" => "First", "id" => 1),
+            pydict!(py, "name" => "First", "id" => 2),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_join_multiple_keys_with_residual_filter() {
+    let query = r#"
+    SELECT stats.day, stats.spend, budget.amount
+    FROM stats
+    LEFT JOIN budget
+        ON stats.campaign_id = budget.campaign_id
+        AND budget.day = stats.day
+        AND stats.spend < budget.amount
+    "#;
+    Python::with_gil(|py| {
+        let stats = vec![
+            pydict!(py, "campaign_id" => 10, "day" => 1, "spend" => 10),
+            pydict!(py, "campaign_id" => 10, "day" => 2, "spend" => 20),
+            pydict!(py, "campaign_id" => 20, "day" => 1, "spend" => 30),
+        ];
+        let budget = vec![
+            pydict!(py, "campaign_id" => 10, "day" => 1, "amount" => 15),
+            pydict!(py, "campaign_id" => 10, "day" => 2, "amount" => 15),
+            pydict!(py, "campaign_id" => 20, "day" => 2, "amount" => 50),
+        ];
+        let tables = pydict!(py, "stats" => stats, "budget" => budget);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![
+            pydict!(py, "day" => 1, "spend" => 10, "amount" => 15),
+            pydict!(py, "day" => 2, "spend" => 20, "amount" => py.None()),
+            pydict!(py, "day" => 1, "spend" => 30, "amount" => py.None()),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_join_using(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT ad_data.id, campaign.name
+    FROM ad_data
+    JOIN campaign USING (campaign_id)
+    "#;
+    Python::with_gil(|py| {
+        let campaign = vec![pydict!(py, "campaign_id" => 20, "name" => "Second")];
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign" => campaign);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![pydict!(py, "id" => 3, "name" => "Second")];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_cross_join(ad_data: &Py<PyList>, campaign_data: &Py<PyList>) {
+    let query = r#"
+    SELECT ad_data.id, campaign_data.name
+    FROM ad_data
+    CROSS JOIN campaign_data
+    WHERE ad_data.spend > 10
+    "#;
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign_data" => campaign_data);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 2, "name" => "First"),
+            pydict!(py, "id" => 2, "name" => "Third"),
+            pydict!(py, "id" => 3, "name" => "First"),
+            pydict!(py, "id" => 3, "name" => "Third"),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_range_join() {
+    let query = r#"
+    SELECT events.id, windows.name
+    FROM events
+    LEFT JOIN windows ON events.ts BETWEEN windows.start AND windows.end
+    "#;
+    Python::with_gil(|py| {
+        let events = vec![
+            pydict!(py, "id" => 1, "ts" => 5),
+            pydict!(py, "id" => 2, "ts" => 10),
+            pydict!(py, "id" => 3, "ts" => 25),
+        ];
+        let windows = vec![
+            pydict!(py, "name" => "a", "start" => 0, "end" => 10),
+            pydict!(py, "name" => "b", "start" => 10, "end" => 20),
+        ];
+        let tables = pydict!(py, "events" => events, "windows" => windows);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "name" => "a"),
+            pydict!(py, "id" => 2, "name" => "a"),
+            pydict!(py, "id" => 2, "name" => "b"),
+            pydict!(py, "id" => 3, "name" => py.None()),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_join_int_to_float_keys() {
+    let query = r#"
+    SELECT orders.id, prices.price
+    FROM orders
+    JOIN prices ON orders.quantity = prices.quantity
+    "#;
+    Python::with_gil(|py| {
+        let orders = vec![
+            pydict!(py, "id" => 1, "quantity" => 1),
+            pydict!(py, "id" => 2, "quantity" => 2),
+            pydict!(py, "id" => 3, "quantity" => 3),
+        ];
+        let prices = vec![
+            pydict!(py, "quantity" => 1.0, "price" => 10),
+            pydict!(py, "quantity" => 2.5, "price" => 20),
+            pydict!(py, "quantity" => 3.0, "price" => 30),
+        ];
+        let tables = pydict!(py, "orders" => orders, "prices" => prices);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "price" => 10),
+            pydict!(py, "id" => 3, "price" => 30),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
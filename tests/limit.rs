@@ -0,0 +1,48 @@
+use pyo3::{
+    types::{PyList, PySet},
+    Py, Python,
+};
+use rstest::*;
+
+mod utils;
+use utils::*;
+
+#[rstest]
+#[case("LIMIT 2", vec![1, 2])]
+#[case("LIMIT 2 OFFSET 1", vec![2, 3])]
+#[case("OFFSET 2", vec![3])]
+#[case("LIMIT 0", vec![])]
+fn test_limit_offset(ad_data: &Py<PyList>, #[case] limit: &str, #[case] expected: Vec<i64>) {
+    let query = format!("SELECT id FROM dataset {}", limit);
+    Python::with_gil(|py| {
+        let result = sqc::query(py, &query, ad_data.bind(py), None).unwrap();
+        let expected: Vec<_> = expected.into_iter().map(|id| pydict!(py, "id" => id)).collect();
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_limit_stops_early() {
+    // the last row can't be evaluated, so it must never be pulled from the input
+    let query = r#"
+    SELECT id
+    FROM dataset
+    WHERE campaign_id = 10
+    LIMIT 2
+    "#;
+    Python::with_gil(|py| {
+        let unsupported = PySet::new_bound(py, &[1, 2]).unwrap();
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "id" => 1, "campaign_id" => 10),
+                pydict!(py, "id" => 2, "campaign_id" => 20),
+                pydict!(py, "id" => 3, "campaign_id" => 10),
+                pydict!(py, "id" => 4, "campaign_id" => unsupported),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![pydict!(py, "id" => 1), pydict!(py, "id" => 3)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
@@ -0,0 +1,145 @@
+use pyo3::{
+    exceptions::PyRuntimeError,
+    types::{PyDict, PyNone},
+    Bound, Python,
+};
+use rstest::*;
+
+mod utils;
+
+fn employees(py: Python<'_>) -> Bound<'_, PyDict> {
+    let employees = vec![
+        pydict!(py, "id" => 1, "name" => "CEO", "manager_id" => py.None()),
+        pydict!(py, "id" => 2, "name" => "CTO", "manager_id" => 1),
+        pydict!(py, "id" => 3, "name" => "Engineer", "manager_id" => 2),
+        pydict!(py, "id" => 4, "name" => "CFO", "manager_id" => 1),
+    ];
+    pydict!(py, "employees" => employees)
+}
+
+#[rstest]
+fn test_recursive_managers_chain() {
+    let query = r#"
+    WITH RECURSIVE chain AS (
+        SELECT id, name, manager_id, 0 AS depth FROM employees WHERE id = 3
+        UNION ALL
+        SELECT e.id, e.name, e.manager_id, chain.depth + 1
+        FROM employees e
+        JOIN chain ON e.id = chain.manager_id
+    )
+    SELECT name, depth FROM chain
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, &employees(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "name" => "Engineer", "depth" => 0),
+            pydict!(py, "name" => "CTO", "depth" => 1),
+            pydict!(py, "name" => "CEO", "depth" => 2),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_recursive_subordinates() {
+    let query = r#"
+    WITH RECURSIVE subordinates AS (
+        SELECT id, name FROM employees WHERE manager_id = 1
+        UNION ALL
+        SELECT e.id, e.name
+        FROM employees e
+        JOIN subordinates s ON e.manager_id = s.id
+    )
+    SELECT name FROM subordinates
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, &employees(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "name" => "CTO"),
+            pydict!(py, "name" => "CFO"),
+            pydict!(py, "name" => "Engineer"),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_recursive_with_limit() {
+    let query = r#"
+    WITH RECURSIVE numbers AS (
+        SELECT 1 AS n
+        UNION ALL
+        SELECT n + 1 FROM numbers
+    )
+    SELECT n FROM numbers LIMIT 3
+    "#;
+    Python::with_gil(|py| {
+        let data = PyNone::get_bound(py);
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "n" => 1),
+            pydict!(py, "n" => 2),
+            pydict!(py, "n" => 3),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_recursive_max_recursion() {
+    let query = r#"
+    WITH RECURSIVE numbers AS (
+        SELECT 1 AS n
+        UNION ALL
+        SELECT n + 1 FROM numbers
+    )
+    SELECT n FROM numbers
+    "#;
+    Python::with_gil(|py| {
+        let data = PyNone::get_bound(py);
+        let error = sqc::query(py, query, &data, Some(5)).unwrap_err();
+        assert!(error.is_instance_of::<PyRuntimeError>(py));
+        assert!(error
+            .to_string()
+            .contains(r#"MaxRecursion { name: "numbers", max_recursion: 5 }"#));
+    });
+}
+
+#[rstest]
+fn test_recursive_scalar_subquery_over_work_table() {
+    let query = r#"
+    WITH RECURSIVE numbers AS (
+        SELECT 1 AS n
+        UNION ALL
+        SELECT n + 1 FROM numbers WHERE n < 5 AND (SELECT max(n) FROM numbers) < 3
+    )
+    SELECT n FROM numbers
+    "#;
+    Python::with_gil(|py| {
+        let data = PyNone::get_bound(py);
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected: Vec<_> = (1..=3).map(|n| pydict!(py, "n" => n)).collect();
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_recursive_cte_referenced_twice_in_recursive_term() {
+    let query = r#"
+    WITH RECURSIVE numbers AS (
+        SELECT 1 AS n
+        UNION ALL
+        (
+            WITH previous AS (SELECT n FROM numbers)
+            SELECT a.n + 1 FROM previous a JOIN previous b ON a.n = b.n WHERE a.n < 3
+        )
+    )
+    SELECT n FROM numbers
+    "#;
+    Python::with_gil(|py| {
+        let data = PyNone::get_bound(py);
+        let result = sqc::query(py, query, &data, Some(10)).unwrap();
+        let expected: Vec<_> = (1..=3).map(|n| pydict!(py, "n" => n)).collect();
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
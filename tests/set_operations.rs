@@ -0,0 +1,52 @@
+use pyo3::{types::PyDict, Bound, Python};
+use rstest::*;
+
+mod utils;
+
+fn snapshots(py: Python<'_>) -> Bound<'_, PyDict> {
+    let old = vec![
+        pydict!(py, "id" => 1),
+        pydict!(py, "id" => 1),
+        pydict!(py, "id" => 2),
+        pydict!(py, "id" => 3),
+    ];
+    let new = vec![
+        pydict!(py, "id" => 1),
+        pydict!(py, "id" => 3),
+        pydict!(py, "id" => 4),
+    ];
+    pydict!(py, "old" => old, "new" => new)
+}
+
+#[rstest]
+#[case("UNION ALL", vec![1, 1, 2, 3, 1, 3, 4])]
+#[case("UNION", vec![1, 2, 3, 4])]
+#[case("INTERSECT", vec![1, 3])]
+#[case("INTERSECT ALL", vec![1, 3])]
+#[case("EXCEPT", vec![2])]
+#[case("EXCEPT ALL", vec![1, 2])]
+fn test_set_operations(#[case] op: &str, #[case] expected: Vec<i64>) {
+    let query = format!("SELECT id FROM old {} SELECT id FROM new", op);
+    Python::with_gil(|py| {
+        let result = sqc::query(py, &query, &snapshots(py), None).unwrap();
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|id| pydict!(py, "id" => id))
+            .collect();
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_union_column_names() {
+    let query = r#"
+    SELECT id AS old_id FROM old WHERE id = 2
+    UNION ALL
+    SELECT id AS new_id FROM new WHERE id = 4
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, &snapshots(py), None).unwrap();
+        let expected = vec![pydict!(py, "old_id" => 2), pydict!(py, "old_id" => 4)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
@@ -12,7 +12,7 @@ fn test_select_constants() {
     "#;
     Python::with_gil(|py| {
         let data = PyNone::get_bound(py);
-        let result = sqc::query(py, query, &data).unwrap();
+        let result = sqc::query(py, query, &data, None).unwrap();
         let expected = vec![pydict!(py, "a" => 10)];
         pyo3::py_run!(py, result expected, r#"assert result == expected"#);
     })
@@ -26,7 +26,7 @@ fn test_select_with_filter(ad_data: &Py<PyList>) {
     WHERE campaign_id = 20
     "#;
     Python::with_gil(|py| {
-        let result = sqc::query(py, query, ad_data.bind(py)).unwrap();
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
         let expected = vec![pydict!(py, "ID" => 3, "Spend, $" => 30)];
         pyo3::py_run!(py, result expected, r#"assert result == expected"#);
     })
@@ -0,0 +1,46 @@
+use pyo3::{types::PyList, Py, Python};
+use rstest::*;
+
+mod utils;
+use utils::*;
+
+#[rstest]
+fn test_order_by_multiple_keys(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT id, campaign_id
+    FROM dataset
+    ORDER BY campaign_id DESC, spend
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 3, "campaign_id" => 20),
+            pydict!(py, "id" => 1, "campaign_id" => 10),
+            pydict!(py, "id" => 2, "campaign_id" => 10),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+#[case("ASC NULLS FIRST", vec![4, 2, 1, 3])]
+#[case("ASC NULLS LAST", vec![2, 1, 3, 4])]
+#[case("DESC", vec![4, 3, 1, 2])]
+#[case("DESC NULLS LAST", vec![3, 1, 2, 4])]
+fn test_order_by_nulls_and_mixed_numbers(#[case] order: &str, #[case] expected: Vec<i64>) {
+    let query = format!("SELECT id FROM dataset ORDER BY value {}", order);
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "id" => 1, "value" => 2.5),
+                pydict!(py, "id" => 2, "value" => 1),
+                pydict!(py, "id" => 3, "value" => 3),
+                pydict!(py, "id" => 4, "value" => py.None()),
+            ],
+        );
+        let result = sqc::query(py, &query, &data, None).unwrap();
+        let expected: Vec<_> = expected.into_iter().map(|id| pydict!(py, "id" => id)).collect();
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
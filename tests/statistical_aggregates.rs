@@ -0,0 +1,222 @@
+use pyo3::{exceptions::PyRuntimeError, types::PyList, Python};
+use rstest::*;
+
+mod utils;
+
+fn values<'p>(py: Python<'p>, column: &str, values: &[i64]) -> pyo3::Bound<'p, PyList> {
+    PyList::new_bound(py, values.iter().map(|v| pydict!(py, column => v)))
+}
+
+#[rstest]
+fn test_percentiles() {
+    let query = r#"
+    SELECT
+        endpoint,
+        percentile_cont(0.9) WITHIN GROUP (ORDER BY latency) AS p90,
+        percentile_disc(0.9) WITHIN GROUP (ORDER BY latency) AS p90_disc,
+        percentile_cont(0.9) WITHIN GROUP (ORDER BY latency DESC) AS p90_desc,
+        median(latency) AS median
+    FROM dataset
+    GROUP BY endpoint
+    "#;
+    Python::with_gil(|py| {
+        let data: Vec<_> = [2, 4, 4, 4, 5, 5, 7, 9]
+            .iter()
+            .map(|latency| pydict!(py, "endpoint" => "/a", "latency" => latency))
+            .chain([
+                pydict!(py, "endpoint" => "/b", "latency" => 100),
+                pydict!(py, "endpoint" => "/b", "latency" => None::<i64>),
+            ])
+            .collect();
+        let data = PyList::new_bound(py, data);
+        let result = sqc::query(py, query, &data, None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"
+from math import isclose
+a, b = result
+assert a["endpoint"] == "/a" and b["endpoint"] == "/b"
+assert isclose(a["p90"], 7.6) and isclose(a["p90_desc"], 3.4)
+assert a["p90_disc"] == 9 and a["median"] == 4.5
+assert b["p90"] == 100.0 and b["p90_disc"] == 100 and b["median"] == 100.0
+"#
+        );
+    });
+}
+
+#[rstest]
+fn test_within_group_after_comments_and_strings() {
+    let query = r#"
+    SELECT /* p50 of x's */
+        'within group (' AS label,
+        percentile_cont(0.5) -- of x's values )
+            WITHIN /* ) */ GROUP (ORDER BY x) AS p50
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let data = values(py, "x", &[1, 2, 4]);
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![pydict!(py, "label" => "within group (", "p50" => 2.0)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_variance_and_stddev() {
+    let query = r#"
+    SELECT
+        stddev(x) AS stddev,
+        stddev_pop(x) AS stddev_pop,
+        variance(x) AS variance,
+        var_pop(x) AS var_pop
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let data = values(py, "x", &[2, 4, 4, 4, 5, 5, 7, 9]);
+        let result = sqc::query(py, query, &data, None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"
+from math import isclose, sqrt
+[row] = result
+assert isclose(row["variance"], 32 / 7) and isclose(row["stddev"], sqrt(32 / 7))
+assert isclose(row["var_pop"], 4.0) and isclose(row["stddev_pop"], 2.0)
+"#
+        );
+    });
+}
+
+#[rstest]
+fn test_statistics_with_distinct_and_filter() {
+    let query = r#"
+    SELECT
+        median(x) AS median,
+        median(DISTINCT x) AS median_distinct,
+        var_pop(DISTINCT x) AS var_pop_distinct,
+        stddev_pop(x) FILTER (WHERE x > 1) AS stddev_pop_filtered,
+        corr(x, x) FILTER (WHERE x > 1) AS corr_filtered
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let data = values(py, "x", &[1, 1, 1, 3, 5]);
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![pydict!(
+            py,
+            "median" => 1.0,
+            "median_distinct" => 3.0,
+            "var_pop_distinct" => 8.0 / 3.0,
+            "stddev_pop_filtered" => 1.0,
+            "corr_filtered" => 1.0
+        )];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_distinct_on_unsupported_aggregate() {
+    let query = "SELECT covar_samp(DISTINCT x, x) FROM dataset";
+    Python::with_gil(|py| {
+        let data = values(py, "x", &[1, 2]);
+        let error = sqc::query(py, query, &data, None).unwrap_err();
+        assert!(error.is_instance_of::<PyRuntimeError>(py));
+        assert!(error
+            .to_string()
+            .contains("DISTINCT is not supported for covar_samp"));
+    });
+}
+
+#[rstest]
+fn test_sample_statistics_of_one_row() {
+    let query = "SELECT stddev(x) AS stddev, var_pop(x) AS var_pop FROM dataset";
+    Python::with_gil(|py| {
+        let data = values(py, "x", &[3]);
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![pydict!(py, "stddev" => None::<f64>, "var_pop" => 0.0)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_correlation_and_covariance() {
+    let query = r#"
+    SELECT
+        corr(x, y) AS corr,
+        covar_samp(x, y) AS covar_samp,
+        covar_pop(x, y) AS covar_pop
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            [1, 2, 3, 4]
+                .iter()
+                .map(|x| pydict!(py, "x" => x, "y" => 2 * x)),
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"
+from math import isclose
+[row] = result
+assert isclose(row["corr"], 1.0)
+assert isclose(row["covar_samp"], 10 / 3) and isclose(row["covar_pop"], 2.5)
+"#
+        );
+    });
+}
+
+#[rstest]
+fn test_approx_distinct() {
+    let query = r#"
+    SELECT
+        approx_distinct(user_id) AS users,
+        approx_distinct(user_id / 1000) AS few,
+        count(DISTINCT user_id) AS exact
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        // every user twice
+        let user_ids: Vec<i64> = (0..20_000).map(|i| i % 10_000).collect();
+        let data = values(py, "user_id", &user_ids);
+        let result = sqc::query(py, query, &data, None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"
+[row] = result
+assert row["exact"] == 10_000
+assert abs(row["users"] - 10_000) < 500, row["users"]
+assert row["few"] == 10
+"#
+        );
+    });
+}
+
+#[rstest]
+fn test_approx_percentile_cont() {
+    let query = r#"
+    SELECT
+        approx_percentile_cont(latency, 0.5) AS p50,
+        approx_percentile_cont(latency, 0.99) AS p99,
+        approx_percentile_cont(latency, 0.9, 200) AS p90
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let latencies: Vec<i64> = (1..=10_000).rev().collect();
+        let data = values(py, "latency", &latencies);
+        let result = sqc::query(py, query, &data, None).unwrap();
+        pyo3::py_run!(
+            py,
+            result,
+            r#"
+[row] = result
+assert isinstance(row["p50"], int) and abs(row["p50"] - 5_000) <= 50, row
+assert abs(row["p99"] - 9_900) <= 20, row
+assert abs(row["p90"] - 9_000) <= 50, row
+"#
+        );
+    });
+}
@@ -0,0 +1,135 @@
+use pyo3::{types::PyList, Py, Python};
+use rstest::*;
+
+mod utils;
+use utils::*;
+
+#[rstest]
+#[case("IN", vec![1, 2])]
+#[case("NOT IN", vec![3])]
+fn test_in_subquery(
+    ad_data: &Py<PyList>,
+    campaign_data: &Py<PyList>,
+    #[case] op: &str,
+    #[case] expected: Vec<i64>,
+) {
+    let query = format!(
+        r#"
+        SELECT id
+        FROM ad_data
+        WHERE campaign_id {} (SELECT id FROM campaign_data)
+        "#,
+        op
+    );
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign_data" => campaign_data);
+        let result = sqc::query(py, &query, &tables, None).unwrap();
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|id| pydict!(py, "id" => id))
+            .collect();
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+#[case("EXISTS", vec!["First"])]
+#[case("NOT EXISTS", vec!["Third"])]
+fn test_correlated_exists(
+    ad_data: &Py<PyList>,
+    campaign_data: &Py<PyList>,
+    #[case] op: &str,
+    #[case] expected: Vec<&str>,
+) {
+    let query = format!(
+        r#"
+        SELECT name
+        FROM campaign_data
+        WHERE {} (
+            SELECT 1 FROM ad_data
+            WHERE ad_data.campaign_id = campaign_data.id AND ad_data.spend > 10
+        )
+        "#,
+        op
+    );
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign_data" => campaign_data);
+        let result = sqc::query(py, &query, &tables, None).unwrap();
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|name| pydict!(py, "name" => name))
+            .collect();
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_correlated_scalar_subquery(ad_data: &Py<PyList>, campaign_data: &Py<PyList>) {
+    let query = r#"
+    SELECT
+        c.name,
+        (SELECT sum(a.spend) FROM ad_data a WHERE a.campaign_id = c.id) AS spend
+    FROM campaign_data c
+    "#;
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign_data" => campaign_data);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![
+            pydict!(py, "name" => "First", "spend" => 30),
+            pydict!(py, "name" => "Third", "spend" => py.None()),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_scalar_subquery_in_where(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT id
+    FROM ad_data
+    WHERE spend * 3 > (SELECT sum(spend) FROM ad_data)
+    "#;
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![pydict!(py, "id" => 3)];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_subqueries_as_semi_joins(ad_data: &Py<PyList>, campaign_data: &Py<PyList>) {
+    // several matching ads per campaign, each campaign is returned once
+    let query = r#"
+    SELECT name
+    FROM campaign_data c
+    WHERE c.id IN (SELECT campaign_id FROM ad_data)
+        AND EXISTS (SELECT 1 FROM ad_data a WHERE a.campaign_id = c.id AND a.spend > c.id)
+        AND NOT EXISTS (SELECT 1 FROM ad_data a WHERE a.campaign_id = c.id AND a.spend > 20)
+    "#;
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign_data" => campaign_data);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![pydict!(py, "name" => "First")];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_nested_correlated_subqueries(ad_data: &Py<PyList>, campaign_data: &Py<PyList>) {
+    let query = r#"
+    SELECT c.name
+    FROM campaign_data c
+    WHERE EXISTS (
+        SELECT 1 FROM ad_data a
+        WHERE a.campaign_id = c.id
+            AND a.id IN (SELECT b.id FROM ad_data b WHERE b.spend = a.spend AND b.spend < 15)
+    )
+    "#;
+    Python::with_gil(|py| {
+        let tables = pydict!(py, "ad_data" => ad_data, "campaign_data" => campaign_data);
+        let result = sqc::query(py, query, &tables, None).unwrap();
+        let expected = vec![pydict!(py, "name" => "First")];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
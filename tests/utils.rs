@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use pyo3::{types::PyList, Py, Python};
 use rstest::*;
 
@@ -30,3 +32,15 @@ pub fn ad_data() -> Py<PyList> {
         PyList::new_bound(py, data).into()
     })
 }
+
+#[fixture]
+#[once]
+pub fn campaign_data() -> Py<PyList> {
+    Python::with_gil(|py| {
+        let data = vec![
+            pydict!(py, "id" => 10, "name" => "First"),
+            pydict!(py, "id" => 30, "name" => "Third"),
+        ];
+        PyList::new_bound(py, data).into()
+    })
+}
@@ -0,0 +1,269 @@
+use pyo3::{
+    exceptions::PyRuntimeError,
+    types::{PyDate, PyList},
+    Py, Python,
+};
+use rstest::*;
+
+mod utils;
+use utils::*;
+
+#[rstest]
+fn test_top_n_per_group(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT campaign_id, id
+    FROM (
+        SELECT
+            campaign_id,
+            id,
+            ROW_NUMBER() OVER (PARTITION BY campaign_id ORDER BY spend DESC) AS rn
+        FROM dataset
+    ) t
+    WHERE rn = 1
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "campaign_id" => 10, "id" => 2),
+            pydict!(py, "campaign_id" => 20, "id" => 3),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_ranking_functions() {
+    let query = r#"
+    SELECT
+        id,
+        RANK() OVER (ORDER BY score DESC) AS rank,
+        DENSE_RANK() OVER (ORDER BY score DESC) AS dense_rank,
+        PERCENT_RANK() OVER (ORDER BY score DESC) AS percent_rank,
+        NTILE(2) OVER (ORDER BY score DESC) AS ntile
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "id" => 1, "score" => 10),
+                pydict!(py, "id" => 2, "score" => 30),
+                pydict!(py, "id" => 3, "score" => 20),
+                pydict!(py, "id" => 4, "score" => 30),
+                pydict!(py, "id" => 5, "score" => 5),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "rank" => 4, "dense_rank" => 3, "percent_rank" => 0.75, "ntile" => 2),
+            pydict!(py, "id" => 2, "rank" => 1, "dense_rank" => 1, "percent_rank" => 0.0, "ntile" => 1),
+            pydict!(py, "id" => 3, "rank" => 3, "dense_rank" => 2, "percent_rank" => 0.5, "ntile" => 1),
+            pydict!(py, "id" => 4, "rank" => 1, "dense_rank" => 1, "percent_rank" => 0.0, "ntile" => 1),
+            pydict!(py, "id" => 5, "rank" => 5, "dense_rank" => 4, "percent_rank" => 1.0, "ntile" => 2),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_running_total(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT
+        id,
+        SUM(spend) OVER (PARTITION BY campaign_id ORDER BY id) AS running_spend,
+        SUM(spend) OVER (PARTITION BY campaign_id) AS campaign_spend,
+        COUNT(*) OVER (ORDER BY id) AS running_count
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "running_spend" => 10, "campaign_spend" => 30, "running_count" => 1),
+            pydict!(py, "id" => 2, "running_spend" => 30, "campaign_spend" => 30, "running_count" => 2),
+            pydict!(py, "id" => 3, "running_spend" => 30, "campaign_spend" => 30, "running_count" => 3),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_rows_frame() {
+    let query = r#"
+    SELECT
+        id,
+        SUM(value) OVER (ORDER BY id ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING) AS sum,
+        AVG(value) OVER (ORDER BY id ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) AS avg,
+        MIN(value) OVER (ORDER BY id ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) AS min,
+        MAX(value) OVER (ORDER BY id ROWS BETWEEN CURRENT ROW AND 2 FOLLOWING) AS max,
+        COUNT(value) OVER (ORDER BY id ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING) AS count
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "id" => 1, "value" => 4),
+                pydict!(py, "id" => 2, "value" => 2),
+                pydict!(py, "id" => 3, "value" => None::<i64>),
+                pydict!(py, "id" => 4, "value" => 8),
+                pydict!(py, "id" => 5, "value" => 1),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "sum" => 6, "avg" => 4.0, "min" => 4, "max" => 4, "count" => 0),
+            pydict!(py, "id" => 2, "sum" => 6, "avg" => 3.0, "min" => 2, "max" => 8, "count" => 1),
+            pydict!(py, "id" => 3, "sum" => 10, "avg" => 2.0, "min" => 2, "max" => 8, "count" => 2),
+            pydict!(py, "id" => 4, "sum" => 9, "avg" => 8.0, "min" => 2, "max" => 8, "count" => 2),
+            pydict!(py, "id" => 5, "sum" => 9, "avg" => 4.5, "min" => 1, "max" => 1, "count" => 3),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_mixed_numeric_frame() {
+    let query = r#"
+    SELECT
+        id,
+        SUM(value) OVER (ORDER BY id) AS total,
+        SUM(value) OVER (ORDER BY id ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) AS sum,
+        AVG(value) OVER (ORDER BY id ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) AS avg,
+        MIN(value) OVER (ORDER BY id) AS min,
+        MAX(value) OVER (ORDER BY id) AS max
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "id" => 1, "value" => 1),
+                pydict!(py, "id" => 2, "value" => 2.5),
+                pydict!(py, "id" => 3, "value" => 3),
+                pydict!(py, "id" => 4, "value" => 0.25),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "total" => 1, "sum" => 1, "avg" => 1.0, "min" => 1, "max" => 1),
+            pydict!(py, "id" => 2, "total" => 3.5, "sum" => 3.5, "avg" => 1.75, "min" => 1, "max" => 2.5),
+            pydict!(py, "id" => 3, "total" => 6.5, "sum" => 5.5, "avg" => 2.75, "min" => 1, "max" => 3),
+            pydict!(py, "id" => 4, "total" => 6.75, "sum" => 3.25, "avg" => 1.625, "min" => 0.25, "max" => 3),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_frame_sum_overflow() {
+    let query = "SELECT SUM(value) OVER (ORDER BY id) AS total FROM dataset";
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "id" => 1, "value" => i64::MAX),
+                pydict!(py, "id" => 2, "value" => 1),
+            ],
+        );
+        let error = sqc::query(py, query, &data, None).unwrap_err();
+        assert!(error.is_instance_of::<PyRuntimeError>(py));
+        assert!(error
+            .to_string()
+            .contains(r#"Overflow { function: "SUM", data_type: Int64 }"#));
+    });
+}
+
+#[rstest]
+fn test_range_frame() {
+    let query = r#"
+    SELECT
+        id,
+        SUM(score) OVER (ORDER BY score RANGE BETWEEN 10 PRECEDING AND CURRENT ROW) AS asc_sum,
+        SUM(score) OVER (ORDER BY score DESC RANGE BETWEEN CURRENT ROW AND 10 FOLLOWING) AS desc_sum,
+        COUNT(*) OVER (ORDER BY score) AS peers_count
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "id" => 1, "score" => 10),
+                pydict!(py, "id" => 2, "score" => 20),
+                pydict!(py, "id" => 3, "score" => 20),
+                pydict!(py, "id" => 4, "score" => 35),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "asc_sum" => 10, "desc_sum" => 10, "peers_count" => 1),
+            pydict!(py, "id" => 2, "asc_sum" => 50, "desc_sum" => 50, "peers_count" => 3),
+            pydict!(py, "id" => 3, "asc_sum" => 50, "desc_sum" => 50, "peers_count" => 3),
+            pydict!(py, "id" => 4, "asc_sum" => 35, "desc_sum" => 35, "peers_count" => 4),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_range_frame_over_dates() {
+    let query = r#"
+    SELECT
+        day,
+        SUM(spend) OVER (ORDER BY day RANGE BETWEEN INTERVAL '1' DAY PRECEDING AND CURRENT ROW) AS spend_2d,
+        spend - LAG(spend) OVER (ORDER BY day) AS delta,
+        LEAD(day, 1, day) OVER (ORDER BY day) AS next_day
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let day = |d| PyDate::new_bound(py, 2024, 3, d).unwrap();
+        let data = PyList::new_bound(
+            py,
+            vec![
+                pydict!(py, "day" => day(1), "spend" => 10),
+                pydict!(py, "day" => day(2), "spend" => 15),
+                pydict!(py, "day" => day(4), "spend" => 12),
+            ],
+        );
+        let result = sqc::query(py, query, &data, None).unwrap();
+        let expected = vec![
+            pydict!(py, "day" => day(1), "spend_2d" => 10, "delta" => None::<i64>, "next_day" => day(2)),
+            pydict!(py, "day" => day(2), "spend_2d" => 25, "delta" => 5, "next_day" => day(4)),
+            pydict!(py, "day" => day(4), "spend_2d" => 12, "delta" => -3, "next_day" => day(4)),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_value_functions(ad_data: &Py<PyList>) {
+    let query = r#"
+    SELECT
+        id,
+        FIRST_VALUE(spend) OVER (PARTITION BY campaign_id ORDER BY id) AS first,
+        LAST_VALUE(spend) OVER (PARTITION BY campaign_id ORDER BY id) AS last,
+        NTH_VALUE(id, 2) OVER (ORDER BY id ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING) AS second,
+        LEAD(id, 2, 0) OVER (ORDER BY id) AS lead
+    FROM dataset
+    "#;
+    Python::with_gil(|py| {
+        let result = sqc::query(py, query, ad_data.bind(py), None).unwrap();
+        let expected = vec![
+            pydict!(py, "id" => 1, "first" => 10, "last" => 10, "second" => 2, "lead" => 3),
+            pydict!(py, "id" => 2, "first" => 10, "last" => 20, "second" => 2, "lead" => 0),
+            pydict!(py, "id" => 3, "first" => 30, "last" => 30, "second" => 2, "lead" => 0),
+        ];
+        pyo3::py_run!(py, result expected, r#"assert result == expected"#);
+    });
+}
+
+#[rstest]
+fn test_filter_on_window_function(ad_data: &Py<PyList>) {
+    let query = "SELECT SUM(spend) FILTER (WHERE id > 1) OVER (ORDER BY id) AS total FROM dataset";
+    Python::with_gil(|py| {
+        let error = sqc::query(py, query, ad_data.bind(py), None).unwrap_err();
+        assert!(error.is_instance_of::<PyRuntimeError>(py));
+        assert!(error
+            .to_string()
+            .contains("FILTER is not supported for window functions"));
+    });
+}